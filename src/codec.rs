@@ -1,8 +1,86 @@
 //! Traits used by the user-defined decoders and encoders.
 //! These are used with [`crate::framed::FramedRead`] and [`crate::framed::FramedWrite`]
+//!
+//! Ready-made codecs for common framing schemes are also provided:
+//! * [`BytesCodec`] for passing raw bytes through unframed
+//! * [`Chained`] for a header decoded by one codec followed by a body
+//!   decoded by another
+//! * [`ChecksummedCodec`] for adding a CRC-32 to the frames of another codec
+//! * [`CobsCodec`] for COBS encoded frames delimited by a zero byte
+//! * `CompressedCodec` for deflate compressing the frames of another codec,
+//!   with the `flate2` feature
+//! * [`Dedup`] for dropping frames that repeat the one before
+//! * [`DelimiterCodec`] for frames ending with any byte sequence
+//! * [`FrozenCodec`] for handing out frames as `Bytes` without copying
+//! * [`FixedLengthCodec`] for streams of fixed-size records
+//! * [`Limited`] for capping the number of frames decoded by another codec
+//! * [`LengthDelimitedCodec`] for frames preceded by a length header
+//! * [`LinesCodec`] for newline delimited text
+//! * [`Multiplexed`] for streams carrying frames on several channels, each
+//!   decoded by its own copy of another codec
+//! * [`SlipCodec`] for SLIP frames, as used on serial links
+//! * `JsonCodec`, `BincodeCodec` and `MsgpackCodec` for types that
+//!   implement `serde`'s `Serialize` and `Deserialize`, with the `json`,
+//!   `bincode` and `rmp-serde` features
+//!
+//! The [`DecoderExt`] and [`EncoderExt`] traits convert the items of any
+//! codec to and from another type or watch them go by, and [`BoxedDecoder`]
+//! and [`BoxedEncoder`] hold a codec chosen at runtime. With the `tokio`
+//! feature, `into_async` wraps any codec for use with `tokio_util`'s framed
+//! types.
 
-use bytes::BytesMut;
-use std::io;
+use alloc::vec::Vec;
+use bytes::{Bytes, BytesMut};
+
+use crate::io::{self, Error, ErrorKind};
+
+pub mod boxed;
+pub mod bytes_codec;
+pub mod chained;
+pub mod checksum;
+pub mod cobs;
+#[cfg(feature = "flate2")]
+pub mod compress;
+pub mod dedup;
+pub mod delimiter;
+pub mod fixed_length;
+pub mod inspect;
+pub mod length_delimited;
+pub mod limited;
+pub mod lines;
+pub mod map;
+pub mod multiplex;
+#[cfg(any(feature = "json", feature = "bincode", feature = "rmp-serde"))]
+pub mod serde_codec;
+pub mod slip;
+#[cfg(feature = "tokio")]
+pub mod tokio_compat;
+
+pub use boxed::{BoxedDecoder, BoxedEncoder};
+pub use bytes_codec::{BytesCodec, FrozenCodec};
+pub use chained::Chained;
+pub use checksum::ChecksummedCodec;
+pub use cobs::CobsCodec;
+#[cfg(feature = "flate2")]
+pub use compress::CompressedCodec;
+pub use dedup::Dedup;
+pub use delimiter::DelimiterCodec;
+pub use fixed_length::FixedLengthCodec;
+pub use inspect::{InspectDecoder, InspectEncoder};
+pub use length_delimited::LengthDelimitedCodec;
+pub use limited::Limited;
+pub use lines::LinesCodec;
+pub use map::{DecoderExt, EncoderExt};
+pub use multiplex::{ChannelId, Multiplexed};
+#[cfg(feature = "bincode")]
+pub use serde_codec::BincodeCodec;
+#[cfg(feature = "json")]
+pub use serde_codec::JsonCodec;
+#[cfg(feature = "rmp-serde")]
+pub use serde_codec::MsgpackCodec;
+pub use slip::SlipCodec;
+#[cfg(feature = "tokio")]
+pub use tokio_compat::{into_async, AsyncCodec};
 
 /// The `Decoder` trait.
 /// Objects that implement this trait take a `BytesMut` and return
@@ -16,13 +94,15 @@ use std::io;
 ///
 /// There are three possibilities to return from this method:
 /// - `Ok(Some(Item))` - in which case decoding is complete and the
-/// user defined `item` is returned.
+///   user defined `item` is returned.
 /// - `Ok(None)`- there is not enough data in `src` to decode the
-/// message. When this is returned `read` is called again on the
-/// underlying `Read` object.
-/// - `Err()` - An error has occurred. This will close the underlying
-/// `Read` object. If you want to indicate a protocol error it is better
-/// to use the user defined `Item` to do this.
+///   message. When this is returned `read` is called again on the
+///   underlying `Read` object.
+/// - `Err()` - An error has occurred. The error is returned by
+///   `framed_read` and the [`crate::FramedRead`] is then unusable: later
+///   reads fail without reading from the underlying `Read` object until
+///   `FramedRead::reset` is called. If you want to indicate a protocol
+///   error it is better to use the user defined `Item` to do this.
 ///
 /// [`Buf`]: https://docs.rs/bytes/1.4.0/bytes/trait.Buf.html
 /// [`BufMut`]: https://docs.rs/bytes/1.4.0/bytes/trait.BufMut.html
@@ -32,6 +112,103 @@ pub trait Decoder {
     type Error: From<io::Error>;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error>;
+
+    /// Called instead of `decode` once the underlying `Read` object
+    /// reports end-of-file.
+    ///
+    /// This gives the decoder one last chance to produce an item from
+    /// whatever remains in `src`, which is useful for protocols that are
+    /// terminated by closing the connection rather than by a length or
+    /// delimiter. If this returns `Ok(None)` then `framed_read` returns an
+    /// error as no more data can arrive.
+    ///
+    /// The default implementation simply calls `decode`.
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode(src)
+    }
+
+    /// Decode a frame into an existing item, replacing its value.
+    ///
+    /// Returns `Ok(true)` if `dst` now holds a newly decoded item and
+    /// `Ok(false)` if there is not yet enough data in `src`, in which case
+    /// `dst` must be left untouched. Codecs whose items own large
+    /// allocations, such as a `Vec`, can override this to clear and refill
+    /// `dst` in place instead of allocating a new item for every frame.
+    ///
+    /// The default implementation calls `decode` and assigns the result.
+    /// This is used by [`crate::FramedReader::framed_read_into`].
+    fn decode_into(
+        &mut self,
+        src: &mut BytesMut,
+        dst: &mut Self::Item,
+    ) -> Result<bool, Self::Error> {
+        match self.decode(src)? {
+            Some(item) => {
+                *dst = item;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Decode every complete frame in `src`.
+    ///
+    /// The default implementation calls `decode` until it returns
+    /// `Ok(None)`. Should `decode` fail, the frames already decoded are
+    /// dropped and the error is returned. This is used by
+    /// [`crate::FramedRead::framed_read_batch`].
+    fn decode_ready(&mut self, src: &mut BytesMut) -> Result<Vec<Self::Item>, Self::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = self.decode(src)? {
+            items.push(item);
+        }
+        Ok(items)
+    }
+
+    /// Returns the total length in bytes of the frame at the start of
+    /// `src`, once enough of it has arrived to tell.
+    ///
+    /// A codec whose frames begin with a length header can implement this
+    /// so that [`crate::FramedRead::frame_progress`] can report how much of
+    /// a large frame has been received. The default implementation returns
+    /// `None`.
+    fn expected_len(&self, _src: &BytesMut) -> Option<usize> {
+        None
+    }
+
+    /// Returns the number of bytes the read buffer should start with, for
+    /// a codec that knows the size of its typical frame.
+    ///
+    /// This is consulted by [`crate::FramedRead::new`], which otherwise
+    /// starts with 8 KiB, so that a codec with larger frames avoids the
+    /// buffer growing while the first of them arrives. As for
+    /// [`crate::FramedRead::with_capacity`], it is also the number of bytes
+    /// requested on each read, so a codec with small frames should leave
+    /// this as the default, which returns `None`.
+    fn capacity_hint(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Decode the single item held by a frame that an adapter codec has already
+/// cut from the stream, such as a decompressed payload.
+pub(crate) fn decode_whole<D: Decoder>(
+    decoder: &mut D,
+    src: &mut BytesMut,
+) -> Result<D::Item, D::Error> {
+    match decoder.decode_eof(src)? {
+        Some(item) if src.is_empty() => Ok(item),
+        Some(_) => Err(Error::new(
+            ErrorKind::InvalidData,
+            "frame holds more than one inner frame",
+        )
+        .into()),
+        None => Err(Error::new(
+            ErrorKind::InvalidData,
+            "frame holds an incomplete inner frame",
+        )
+        .into()),
+    }
 }
 
 /// The `Encoder` trait.
@@ -41,4 +218,102 @@ pub trait Encoder<I> {
     type Error: From<io::Error>;
 
     fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), Self::Error>;
+
+    /// Encode a borrowed item.
+    ///
+    /// The default implementation clones `item` and calls `encode`. Codecs
+    /// that only need to look at the item, such as those writing out a
+    /// byte buffer or serializing it, should override this so that sending
+    /// a large message by reference does not copy it first. This is used by
+    /// [`crate::FramedWriter::framed_write_ref`].
+    fn encode_ref(&mut self, item: &I, dst: &mut BytesMut) -> Result<(), Self::Error>
+    where
+        I: Clone,
+    {
+        self.encode(item.clone(), dst)
+    }
+
+    /// Encode `item` as a sequence of buffers that make up the frame when
+    /// written out one after the other. This is used by
+    /// [`crate::FramedWrite::framed_write_vectored`].
+    ///
+    /// The default implementation encodes into a single buffer with
+    /// `encode`. Codecs whose frames are a small header followed by a large
+    /// payload can override this to push the payload as it is, so that it
+    /// is never copied next to its header.
+    fn encode_vectored(&mut self, item: I, bufs: &mut Vec<Bytes>) -> Result<(), Self::Error> {
+        let mut dst = BytesMut::with_capacity(self.size_hint(&item).unwrap_or(0));
+        self.encode(item, &mut dst)?;
+        bufs.push(dst.freeze());
+        Ok(())
+    }
+
+    /// Returns the number of bytes that encoding `item` will produce, if
+    /// known in advance.
+    ///
+    /// The framed writers reserve this much space before calling `encode`
+    /// so that the destination buffer does not need to grow part way
+    /// through. The default implementation returns `None`.
+    fn size_hint(&self, _item: &I) -> Option<usize> {
+        None
+    }
+}
+
+/// A codec borrowed mutably is a codec itself, so one codec and its state
+/// can be used by several framed readers in turn without being moved.
+impl<D: Decoder + ?Sized> Decoder for &mut D {
+    type Item = D::Item;
+    type Error = D::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        (**self).decode(src)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        (**self).decode_eof(src)
+    }
+
+    fn decode_into(
+        &mut self,
+        src: &mut BytesMut,
+        dst: &mut Self::Item,
+    ) -> Result<bool, Self::Error> {
+        (**self).decode_into(src, dst)
+    }
+
+    fn decode_ready(&mut self, src: &mut BytesMut) -> Result<Vec<Self::Item>, Self::Error> {
+        (**self).decode_ready(src)
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        (**self).expected_len(src)
+    }
+
+    fn capacity_hint(&self) -> Option<usize> {
+        (**self).capacity_hint()
+    }
+}
+
+/// As for decoders, an encoder borrowed mutably is an encoder.
+impl<I, E: Encoder<I> + ?Sized> Encoder<I> for &mut E {
+    type Error = E::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        (**self).encode(item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &I, dst: &mut BytesMut) -> Result<(), Self::Error>
+    where
+        I: Clone,
+    {
+        (**self).encode_ref(item, dst)
+    }
+
+    fn encode_vectored(&mut self, item: I, bufs: &mut Vec<Bytes>) -> Result<(), Self::Error> {
+        (**self).encode_vectored(item, bufs)
+    }
+
+    fn size_hint(&self, item: &I) -> Option<usize> {
+        (**self).size_hint(item)
+    }
 }
@@ -0,0 +1,128 @@
+//! Codecs chosen at runtime.
+//!
+//! [`Decoder`] and [`Encoder`] are object safe, and a boxed codec is a codec
+//! itself, so codecs of different types can be stored behind one type. This
+//! suits applications that pick a codec once a protocol version has been
+//! negotiated:
+//!
+//! ```
+//! # #[cfg(feature = "std")] {
+//! use framous::codec::{BoxedDecoder, DecoderExt, LengthDelimitedCodec, LinesCodec};
+//! use framous::FramedRead;
+//! # use bytes::BytesMut;
+//!
+//! fn decoder_for(version: u8) -> BoxedDecoder<String> {
+//!     match version {
+//!         1 => Box::new(LinesCodec::new()),
+//!         _ => Box::new(
+//!             LengthDelimitedCodec::new()
+//!                 .map(|frame: BytesMut| String::from_utf8_lossy(&frame).into_owned()),
+//!         ),
+//!     }
+//! }
+//!
+//! let framed = FramedRead::new(&b"hello\n"[..], decoder_for(1));
+//! # }
+//! ```
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use bytes::{Bytes, BytesMut};
+
+use crate::io;
+use crate::{Decoder, Encoder};
+
+/// A decoder of any type yielding items of type `I`.
+pub type BoxedDecoder<I, E = io::Error> = Box<dyn Decoder<Item = I, Error = E>>;
+
+/// An encoder of any type accepting items of type `I`.
+pub type BoxedEncoder<I, E = io::Error> = Box<dyn Encoder<I, Error = E>>;
+
+impl<D: Decoder + ?Sized> Decoder for Box<D> {
+    type Item = D::Item;
+    type Error = D::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        (**self).decode(src)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        (**self).decode_eof(src)
+    }
+
+    fn decode_into(
+        &mut self,
+        src: &mut BytesMut,
+        dst: &mut Self::Item,
+    ) -> Result<bool, Self::Error> {
+        (**self).decode_into(src, dst)
+    }
+
+    fn decode_ready(&mut self, src: &mut BytesMut) -> Result<Vec<Self::Item>, Self::Error> {
+        (**self).decode_ready(src)
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        (**self).expected_len(src)
+    }
+
+    fn capacity_hint(&self) -> Option<usize> {
+        (**self).capacity_hint()
+    }
+}
+
+impl<I, E: Encoder<I> + ?Sized> Encoder<I> for Box<E> {
+    type Error = E::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        (**self).encode(item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &I, dst: &mut BytesMut) -> Result<(), Self::Error>
+    where
+        I: Clone,
+    {
+        (**self).encode_ref(item, dst)
+    }
+
+    fn encode_vectored(&mut self, item: I, bufs: &mut Vec<Bytes>) -> Result<(), Self::Error> {
+        (**self).encode_vectored(item, bufs)
+    }
+
+    fn size_hint(&self, item: &I) -> Option<usize> {
+        (**self).size_hint(item)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::codec::{CobsCodec, LengthDelimitedCodec};
+    use crate::{FramedRead, FramedReader, FramedWrite, FramedWriter};
+
+    fn codec(cobs: bool) -> (BoxedDecoder<BytesMut>, BoxedEncoder<BytesMut>) {
+        if cobs {
+            (Box::new(CobsCodec::new()), Box::new(CobsCodec::new()))
+        } else {
+            (
+                Box::new(LengthDelimitedCodec::new()),
+                Box::new(LengthDelimitedCodec::new()),
+            )
+        }
+    }
+
+    #[test]
+    fn codec_chosen_at_runtime() {
+        for cobs in [false, true] {
+            let (decoder, encoder) = codec(cobs);
+            let mut framed = FramedWrite::new(vec![], encoder);
+            framed.framed_write(BytesMut::from(&b"a\0b"[..])).unwrap();
+            let wire = framed.into_inner();
+            assert_eq!(wire.len(), if cobs { 5 } else { 7 });
+
+            let mut framed = FramedRead::new(&wire[..], decoder);
+            assert_eq!(&framed.framed_read().unwrap()[..], b"a\0b");
+            assert!(framed.framed_read().is_err());
+        }
+    }
+}
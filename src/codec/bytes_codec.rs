@@ -0,0 +1,236 @@
+//! A codec that passes raw bytes straight through.
+
+use alloc::vec::Vec;
+use bytes::{Bytes, BytesMut};
+
+use crate::io;
+use crate::{Decoder, Encoder};
+
+/// Moves raw bytes without any framing.
+///
+/// Decoding yields every byte currently in the read buffer as one item, so
+/// the size of each item depends on how much data each `read` returned
+/// rather than on any boundary the peer intended. This suits proxies and
+/// tunnels where the bytes themselves are all that matter. An empty buffer
+/// yields `None`.
+///
+/// Encoding writes the bytes verbatim.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BytesCodec;
+
+impl BytesCodec {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Decoder for BytesCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(src.split()))
+    }
+}
+
+impl Encoder<BytesMut> for BytesCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: BytesMut, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_ref(&item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &BytesMut, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.extend_from_slice(item);
+        Ok(())
+    }
+
+    fn encode_vectored(
+        &mut self,
+        item: BytesMut,
+        bufs: &mut Vec<Bytes>,
+    ) -> Result<(), Self::Error> {
+        bufs.push(item.freeze());
+        Ok(())
+    }
+
+    fn size_hint(&self, item: &BytesMut) -> Option<usize> {
+        Some(item.len())
+    }
+}
+
+impl Encoder<Bytes> for BytesCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_ref(&item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &Bytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.extend_from_slice(item);
+        Ok(())
+    }
+
+    fn encode_vectored(&mut self, item: Bytes, bufs: &mut Vec<Bytes>) -> Result<(), Self::Error> {
+        bufs.push(item);
+        Ok(())
+    }
+
+    fn size_hint(&self, item: &Bytes) -> Option<usize> {
+        Some(item.len())
+    }
+}
+
+/// Wraps a decoder whose items are `BytesMut` so that it yields frozen
+/// `Bytes` instead.
+///
+/// Freezing does not copy: each item is a reference-counted view of the
+/// `FramedRead`'s read buffer. Frames can be held on to, cloned cheaply and
+/// sent to other threads while reading carries on. Growing or shrinking the
+/// read buffer never disturbs them, as the buffer only reuses its memory
+/// once every frame taken from it has been dropped.
+///
+/// Encoding is passed straight through to the inner codec.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrozenCodec<D = BytesCodec> {
+    inner: D,
+}
+
+impl<D> FrozenCodec<D> {
+    pub fn new(inner: D) -> Self {
+        Self { inner }
+    }
+
+    /// Returns a reference to the inner codec.
+    pub fn get_ref(&self) -> &D {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner codec.
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.inner
+    }
+
+    /// Consumes the `FrozenCodec`, returning the inner codec.
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+}
+
+impl<D: Decoder<Item = BytesMut>> Decoder for FrozenCodec<D> {
+    type Item = Bytes;
+    type Error = D::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.inner.decode(src)?.map(BytesMut::freeze))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.inner.decode_eof(src)?.map(BytesMut::freeze))
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        self.inner.expected_len(src)
+    }
+}
+
+impl<I, D: Encoder<I>> Encoder<I> for FrozenCodec<D> {
+    type Error = D::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.inner.encode(item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &I, dst: &mut BytesMut) -> Result<(), Self::Error>
+    where
+        I: Clone,
+    {
+        self.inner.encode_ref(item, dst)
+    }
+
+    fn encode_vectored(&mut self, item: I, bufs: &mut Vec<Bytes>) -> Result<(), Self::Error> {
+        self.inner.encode_vectored(item, bufs)
+    }
+
+    fn size_hint(&self, item: &I) -> Option<usize> {
+        self.inner.size_hint(item)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::codec::LengthDelimitedCodec;
+    use crate::{FramedRead, FramedReader, FramedWrite, FramedWriter};
+
+    #[test]
+    fn decode_whatever_is_buffered() {
+        let mut codec = BytesCodec::new();
+        let mut src = BytesMut::new();
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        src.extend_from_slice(b"abc");
+        assert_eq!(&codec.decode(&mut src).unwrap().unwrap()[..], b"abc");
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn round_trip_blob() {
+        let blob: Vec<u8> = (0..=255).collect();
+
+        let mut wire = vec![];
+        let mut writer = FramedWrite::new(&mut wire, BytesCodec);
+        writer.framed_write(Bytes::from(blob.clone())).unwrap();
+        writer.framed_write(BytesMut::from(&b"tail"[..])).unwrap();
+        drop(writer);
+
+        let mut received = vec![];
+        for chunk in FramedRead::new(&wire[..], BytesCodec) {
+            received.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(&received[..256], &blob[..]);
+        assert_eq!(&received[256..], b"tail");
+    }
+
+    fn length_delimited_wire(frames: usize) -> Vec<u8> {
+        let mut writer = FramedWrite::new(vec![], FrozenCodec::new(LengthDelimitedCodec::new()));
+        for i in 0..frames {
+            writer
+                .framed_write(BytesMut::from(&[i as u8; 3][..]))
+                .unwrap();
+        }
+        writer.into_inner()
+    }
+
+    #[test]
+    fn frozen_frames_share_the_read_buffer() {
+        let wire = length_delimited_wire(2);
+        let mut reader =
+            FramedRead::with_capacity(&wire[..], FrozenCodec::new(LengthDelimitedCodec::new()), 64);
+        let first = reader.framed_read().unwrap();
+        let second = reader.peek_frame().unwrap().unwrap();
+        assert_eq!(&first[..], &[0, 0, 0]);
+        assert_eq!(&second[..], &[1, 1, 1]);
+        // Both payloads point into the buffer filled by the one read
+        // with just the header in between
+        assert_eq!(first.as_ptr().wrapping_add(3 + 4), second.as_ptr());
+    }
+
+    #[test]
+    fn frozen_frames_survive_buffer_growth() {
+        let wire = length_delimited_wire(200);
+        let mut reader =
+            FramedRead::with_capacity(&wire[..], FrozenCodec::new(LengthDelimitedCodec::new()), 5);
+        reader.set_shrink_threshold(Some(8));
+        let mut frames = vec![];
+        while let Ok(frame) = reader.framed_read() {
+            frames.push(frame);
+        }
+        assert_eq!(frames.len(), 200);
+        for (i, frame) in frames.iter().enumerate() {
+            assert_eq!(&frame[..], &[i as u8; 3]);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! A codec made of a header codec followed by a body codec.
+//!
+//! Many protocols start each frame with a fixed header, such as a message
+//! type, followed by a body framed some other way. [`Chained`] decodes the
+//! header with its first codec and then the body with its second, from the
+//! same buffer:
+//!
+//! ```text
+//! +----------------------+----------------------+
+//! | bytes consumed by A  | bytes consumed by B  |
+//! +----------------------+----------------------+
+//! ```
+//!
+//! The first codec only sees the start of the buffer and consumes the bytes
+//! of the header. Whatever it leaves behind, however much of the stream that
+//! is, is what the second codec decodes the body from. Once the header has
+//! been decoded it is held on to until the body is complete, so neither
+//! codec is asked to decode the same bytes twice.
+//!
+//! The combined item is the pair `(header, body)`. Use
+//! [`DecoderExt::map`](crate::codec::DecoderExt::map) to turn it into a
+//! type of your own.
+
+use alloc::vec::Vec;
+use bytes::{Bytes, BytesMut};
+
+use crate::{Decoder, Encoder};
+
+/// Decodes a header with `A` then a body with `B`, yielding both as a pair.
+///
+/// Both codecs must share an error type.
+#[derive(Debug, Clone, Default)]
+pub struct Chained<A: Decoder, B> {
+    first: A,
+    second: B,
+    header: Option<A::Item>,
+}
+
+impl<A: Decoder, B> Chained<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Self {
+            first,
+            second,
+            header: None,
+        }
+    }
+
+    /// Returns references to the header and body codecs.
+    pub fn get_ref(&self) -> (&A, &B) {
+        (&self.first, &self.second)
+    }
+
+    /// Returns mutable references to the header and body codecs.
+    pub fn get_mut(&mut self) -> (&mut A, &mut B) {
+        (&mut self.first, &mut self.second)
+    }
+
+    /// Returns the header of the frame being decoded, once it has been
+    /// decoded but before the body is complete.
+    pub fn pending_header(&self) -> Option<&A::Item> {
+        self.header.as_ref()
+    }
+
+    /// Consumes the `Chained`, returning the header and body codecs. A
+    /// decoded header still waiting for its body is lost.
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A, B> Decoder for Chained<A, B>
+where
+    A: Decoder,
+    B: Decoder<Error = A::Error>,
+{
+    type Item = (A::Item, B::Item);
+    type Error = A::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if self.header.is_none() {
+            match self.first.decode(src)? {
+                Some(header) => self.header = Some(header),
+                None => return Ok(None),
+            }
+        }
+        Ok(self
+            .second
+            .decode(src)?
+            .map(|body| (self.header.take().unwrap(), body)))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if self.header.is_none() {
+            match self.first.decode_eof(src)? {
+                Some(header) => self.header = Some(header),
+                None => return Ok(None),
+            }
+        }
+        Ok(self
+            .second
+            .decode_eof(src)?
+            .map(|body| (self.header.take().unwrap(), body)))
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        // Only the body's length is known once the header is out of the way
+        self.header.as_ref()?;
+        self.second.expected_len(src)
+    }
+}
+
+impl<A, B, H, I> Encoder<(H, I)> for Chained<A, B>
+where
+    A: Decoder + Encoder<H>,
+    B: Encoder<I, Error = <A as Encoder<H>>::Error>,
+{
+    type Error = <A as Encoder<H>>::Error;
+
+    fn encode(&mut self, (header, body): (H, I), dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.first.encode(header, dst)?;
+        self.second.encode(body, dst)
+    }
+
+    fn encode_vectored(
+        &mut self,
+        (header, body): (H, I),
+        bufs: &mut Vec<Bytes>,
+    ) -> Result<(), Self::Error> {
+        self.first.encode_vectored(header, bufs)?;
+        self.second.encode_vectored(body, bufs)
+    }
+
+    fn size_hint(&self, (header, body): &(H, I)) -> Option<usize> {
+        Some(self.first.size_hint(header)? + self.second.size_hint(body)?)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::codec::LengthDelimitedCodec;
+    use crate::io;
+    use crate::{FramedRead, FramedReader, FramedWrite, FramedWriter};
+    use bytes::{Buf, BufMut};
+
+    // A header of a single message type byte
+    #[derive(Debug, Clone, Copy)]
+    struct TypeCodec;
+
+    impl Decoder for TypeCodec {
+        type Item = u8;
+        type Error = io::Error;
+
+        fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+            Ok(src.has_remaining().then(|| src.get_u8()))
+        }
+    }
+
+    impl Encoder<u8> for TypeCodec {
+        type Error = io::Error;
+
+        fn encode(&mut self, item: u8, dst: &mut BytesMut) -> Result<(), Self::Error> {
+            dst.put_u8(item);
+            Ok(())
+        }
+    }
+
+    fn codec() -> Chained<TypeCodec, LengthDelimitedCodec> {
+        Chained::new(TypeCodec, LengthDelimitedCodec::new())
+    }
+
+    #[test]
+    fn header_then_body() {
+        let mut framed = FramedWrite::new(vec![], codec());
+        framed
+            .framed_write((7, BytesMut::from(&b"hello"[..])))
+            .unwrap();
+        framed.framed_write((9, BytesMut::new())).unwrap();
+        let wire = framed.into_inner();
+        assert_eq!(&wire[..10], b"\x07\x00\x00\x00\x05hello");
+
+        // One byte at a time, so the header arrives before its body
+        let mut framed = FramedRead::with_capacity(&wire[..], codec(), 1);
+        let (kind, body) = framed.framed_read().unwrap();
+        assert_eq!((kind, &body[..]), (7, &b"hello"[..]));
+        let (kind, body) = framed.framed_read().unwrap();
+        assert_eq!((kind, &body[..]), (9, &b""[..]));
+    }
+
+    #[test]
+    fn header_held_until_body_is_complete() {
+        let mut codec = codec();
+        let mut src = BytesMut::from(&[3, 0, 0][..]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        assert_eq!(codec.pending_header(), Some(&3));
+        assert_eq!(&src[..], &[0, 0]);
+
+        src.extend_from_slice(&[0, 1, b'x', 4]);
+        let (kind, body) = codec.decode(&mut src).unwrap().unwrap();
+        assert_eq!((kind, &body[..]), (3, &b"x"[..]));
+        assert_eq!(codec.pending_header(), None);
+        assert_eq!(&src[..], &[4]);
+    }
+}
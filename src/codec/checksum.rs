@@ -0,0 +1,185 @@
+//! A codec adapter that protects each frame with a CRC-32 checksum.
+//!
+//! The inner codec encodes each item as usual and a CRC-32 of the bytes it
+//! produces is appended. The result is framed with a big-endian `u32`
+//! length header, as [`LengthDelimitedCodec`] does, so that the checksum
+//! can always be found:
+//!
+//! ```text
+//! +---------------+---------------------+---------------+
+//! | length (u32)  | inner encoded frame | CRC-32 (u32)  |
+//! +---------------+---------------------+---------------+
+//! ```
+//!
+//! The length counts the inner frame and the checksum but not itself. The
+//! checksum is the common IEEE CRC-32, as used by Ethernet, zlib and PNG,
+//! stored big-endian.
+
+use bytes::{BufMut, BytesMut};
+
+use crate::codec::{decode_whole, LengthDelimitedCodec};
+use crate::io::{Error, ErrorKind};
+use crate::{Decoder, Encoder};
+
+const CHECKSUM_LEN: usize = 4;
+
+const fn make_crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC_TABLE: [u32; 256] = make_crc_table();
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        CRC_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Wraps a codec so that each frame carries a checksum, which is verified
+/// before the frame is handed to the inner decoder.
+///
+/// A frame whose checksum does not match, or that is too short to hold
+/// one, is an error of kind `ErrorKind::InvalidData`.
+#[derive(Debug, Clone)]
+pub struct ChecksummedCodec<C> {
+    inner: C,
+    framing: LengthDelimitedCodec,
+    scratch: BytesMut,
+}
+
+impl<C> ChecksummedCodec<C> {
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            framing: LengthDelimitedCodec::new(),
+            scratch: BytesMut::new(),
+        }
+    }
+
+    /// Returns the maximum length of a frame, including its checksum.
+    pub fn max_frame_length(&self) -> usize {
+        self.framing.max_frame_length()
+    }
+
+    /// Sets the maximum length of a frame, including its checksum.
+    pub fn set_max_frame_length(&mut self, max_frame_length: usize) {
+        self.framing.set_max_frame_length(max_frame_length);
+    }
+
+    /// Returns a reference to the inner codec.
+    pub fn get_ref(&self) -> &C {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner codec.
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+
+    /// Consumes the `ChecksummedCodec`, returning the inner codec.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+impl<C: Decoder> Decoder for ChecksummedCodec<C> {
+    type Item = C::Item;
+    type Error = C::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let mut frame = match self.framing.decode(src)? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+
+        let Some(body_len) = frame.len().checked_sub(CHECKSUM_LEN) else {
+            return Err(
+                Error::new(ErrorKind::InvalidData, "frame is too short for a checksum").into(),
+            );
+        };
+        let checksum = frame.split_off(body_len);
+        if crc32(&frame).to_be_bytes() != checksum[..] {
+            return Err(Error::new(ErrorKind::InvalidData, "frame checksum does not match").into());
+        }
+
+        decode_whole(&mut self.inner, &mut frame).map(Some)
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        self.framing.expected_len(src)
+    }
+}
+
+impl<I, C: Encoder<I>> Encoder<I> for ChecksummedCodec<C> {
+    type Error = C::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.scratch.clear();
+        self.inner.encode(item, &mut self.scratch)?;
+        let checksum = crc32(&self.scratch);
+        self.scratch.put_u32(checksum);
+        self.framing.encode(self.scratch.split(), dst)?;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::codec::LinesCodec;
+    use crate::{FramedRead, FramedReader, FramedWrite, FramedWriter};
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn valid_frames() {
+        let mut framed = FramedWrite::new(vec![], ChecksummedCodec::new(LinesCodec::new()));
+        framed.framed_write("hello").unwrap();
+        framed.framed_write("world").unwrap();
+        let wire = framed.into_inner();
+        assert_eq!(&wire[..10], b"\x00\x00\x00\x0ahello\n");
+
+        let mut framed =
+            FramedRead::with_capacity(&wire[..], ChecksummedCodec::new(LinesCodec::new()), 3);
+        assert_eq!(framed.framed_read().unwrap(), "hello");
+        assert_eq!(framed.framed_read().unwrap(), "world");
+    }
+
+    #[test]
+    fn bit_flipped_payload() {
+        let mut codec = ChecksummedCodec::new(LinesCodec::new());
+        let mut wire = BytesMut::new();
+        codec.encode("hello", &mut wire).unwrap();
+        wire[5] ^= 0x01;
+        let err = codec.decode(&mut wire).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_checksum() {
+        let mut codec = ChecksummedCodec::new(LinesCodec::new());
+        let mut wire = BytesMut::from(&[0, 0, 0, 3, 0xaa, 0xbb, 0xcc][..]);
+        let err = codec.decode(&mut wire).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,171 @@
+//! A codec for Consistent Overhead Byte Stuffing (COBS).
+//!
+//! COBS encodes a payload so that it contains no zero bytes, which leaves
+//! `0x00` free to mark the end of each frame. This makes the stream self
+//! synchronising: a receiver that joins part way through, or that hits a
+//! corrupt frame, only needs to skip to the next zero byte. It is common on
+//! serial links to embedded devices.
+
+use bytes::{BufMut, BytesMut};
+
+use crate::io::{self, Error, ErrorKind};
+use crate::{Decoder, Encoder};
+
+const DELIMITER: u8 = 0;
+// The longest run of non-zero bytes that one code byte can describe
+const MAX_RUN: usize = 0xfe;
+
+/// Splits a stream into COBS encoded frames terminated by a zero byte.
+///
+/// Decoded items are the original payload bytes. A frame that is not valid
+/// COBS is consumed, up to and including its delimiter, and reported as an
+/// error of kind `ErrorKind::InvalidData`, so calling `decode` again carries
+/// on with the next frame. Empty frames, such as a delimiter sent to flush
+/// out line noise, are skipped.
+///
+/// Note that [`crate::FramedRead`] stops reading after any decoder error,
+/// so to carry on past corrupt frames decode from the buffer directly.
+#[derive(Debug, Clone, Default)]
+pub struct CobsCodec {
+    // Where to resume searching for a delimiter on the next call to `decode`
+    next_index: usize,
+}
+
+impl CobsCodec {
+    pub fn new() -> Self {
+        Self { next_index: 0 }
+    }
+}
+
+fn cobs_decode(src: &[u8]) -> io::Result<BytesMut> {
+    let corrupt = || Error::new(ErrorKind::InvalidData, "corrupt COBS frame");
+    let mut dst = BytesMut::with_capacity(src.len());
+    let mut pos = 0;
+    while pos < src.len() {
+        let code = src[pos] as usize;
+        let run = src.get(pos + 1..pos + code).ok_or_else(corrupt)?;
+        dst.extend_from_slice(run);
+        pos += code;
+        if code <= MAX_RUN && pos < src.len() {
+            dst.put_u8(0);
+        }
+    }
+    Ok(dst)
+}
+
+impl Decoder for CobsCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            let start = self.next_index.min(src.len());
+            let end = match src[start..].iter().position(|b| *b == DELIMITER) {
+                Some(offset) => start + offset,
+                None => {
+                    self.next_index = src.len();
+                    return Ok(None);
+                }
+            };
+            self.next_index = 0;
+            let frame = src.split_to(end + 1);
+            if end > 0 {
+                return cobs_decode(&frame[..end]).map(Some);
+            }
+        }
+    }
+}
+
+impl Encoder<BytesMut> for CobsCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: BytesMut, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_ref(&item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &BytesMut, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.reserve(item.len() + item.len() / MAX_RUN + 2);
+        let mut code_index = dst.len();
+        dst.put_u8(1);
+        for &byte in item {
+            if byte != 0 {
+                dst.put_u8(byte);
+                dst[code_index] += 1;
+            }
+            if byte == 0 || dst[code_index] as usize == MAX_RUN + 1 {
+                code_index = dst.len();
+                dst.put_u8(1);
+            }
+        }
+        dst.put_u8(DELIMITER);
+        Ok(())
+    }
+
+    fn size_hint(&self, item: &BytesMut) -> Option<usize> {
+        Some(item.len() + item.len() / MAX_RUN + 2)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{FramedRead, FramedReader};
+
+    fn encode(payload: &[u8]) -> BytesMut {
+        let mut dst = BytesMut::new();
+        CobsCodec::new()
+            .encode(BytesMut::from(payload), &mut dst)
+            .unwrap();
+        dst
+    }
+
+    #[test]
+    fn normal_frame() {
+        assert_eq!(&encode(b"\x11\x22\x33")[..], b"\x04\x11\x22\x33\x00");
+
+        let r = [4u8, 0x11, 0x22, 0x33, 0, 2, 0x44, 0];
+        let mut framed = FramedRead::with_capacity(&r[..], CobsCodec::new(), 3);
+        assert_eq!(&framed.framed_read().unwrap()[..], &[0x11, 0x22, 0x33]);
+        assert_eq!(&framed.framed_read().unwrap()[..], &[0x44]);
+        assert!(framed.framed_read().is_err());
+    }
+
+    #[test]
+    fn zeros_in_payload() {
+        let payload = [0u8, 0x11, 0, 0, 0x22, 0];
+        let wire = encode(&payload);
+        assert_eq!(&wire[..], &[1, 2, 0x11, 1, 2, 0x22, 1, 0]);
+        assert!(!wire[..wire.len() - 1].contains(&0));
+
+        let mut src = wire;
+        let decoded = CobsCodec::new().decode(&mut src).unwrap().unwrap();
+        assert_eq!(&decoded[..], &payload);
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn long_runs() {
+        for len in [253, 254, 255, 600] {
+            let payload: Vec<u8> = (0..len).map(|i| (i % 255 + 1) as u8).collect();
+            let mut src = encode(&payload);
+            assert!(src.len() <= payload.len() + payload.len() / MAX_RUN + 2);
+            let decoded = CobsCodec::new().decode(&mut src).unwrap().unwrap();
+            assert_eq!(&decoded[..], &payload[..]);
+        }
+    }
+
+    #[test]
+    fn recover_after_garbage() {
+        let mut codec = CobsCodec::new();
+        // A code byte promising more bytes than the frame holds
+        let mut src = BytesMut::from(&[9u8, 0xaa, 0xbb][..]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        src.extend_from_slice(&[0, 0]);
+        src.extend_from_slice(&encode(b"ok"));
+
+        let err = codec.decode(&mut src).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(&codec.decode(&mut src).unwrap().unwrap()[..], b"ok");
+        assert!(src.is_empty());
+    }
+}
@@ -0,0 +1,257 @@
+//! A codec adapter that compresses each frame with deflate.
+//!
+//! Only available with the `flate2` feature.
+//!
+//! The inner codec encodes each item as usual and the bytes it produces
+//! are compressed as a raw deflate stream. On the wire every frame is then
+//! a big-endian `u32` holding the compressed length, followed by the
+//! compressed bytes:
+//!
+//! ```text
+//! +---------------+------------------------------+
+//! | length (u32)  | deflate(inner encoded frame) |
+//! +---------------+------------------------------+
+//! ```
+//!
+//! When decoding, the compressed bytes are inflated and handed to the inner
+//! decoder which must produce exactly one item from them.
+
+use bytes::{BufMut, BytesMut};
+use flate2::write::{DeflateDecoder, DeflateEncoder};
+use flate2::Compression;
+use std::io::{self, Error, ErrorKind, Write};
+
+use crate::codec::{decode_whole, LengthDelimitedCodec};
+use crate::{Decoder, Encoder};
+
+/// Wraps a codec so that each frame it produces is compressed.
+///
+/// The length of a compressed frame is limited by
+/// [`LengthDelimitedCodec::max_frame_length`], 8 MiB by default. So that a
+/// small frame from a peer cannot inflate to an enormous one, the length of
+/// a decompressed frame is limited as well, to the same 8 MiB by default.
+#[derive(Debug, Clone)]
+pub struct CompressedCodec<C> {
+    inner: C,
+    framing: LengthDelimitedCodec,
+    level: Compression,
+    max_decompressed_length: usize,
+    scratch: BytesMut,
+}
+
+impl<C> CompressedCodec<C> {
+    /// Wrap `inner` using the default compression level.
+    pub fn new(inner: C) -> Self {
+        Self::with_level(inner, Compression::default().level())
+    }
+
+    /// Wrap `inner` using the given compression level, from 0 (none) to
+    /// 9 (best).
+    pub fn with_level(inner: C, level: u32) -> Self {
+        let framing = LengthDelimitedCodec::new();
+        Self {
+            inner,
+            max_decompressed_length: framing.max_frame_length(),
+            framing,
+            level: Compression::new(level),
+            scratch: BytesMut::new(),
+        }
+    }
+
+    /// Returns the maximum length of a compressed frame.
+    pub fn max_frame_length(&self) -> usize {
+        self.framing.max_frame_length()
+    }
+
+    /// Sets the maximum length of a compressed frame.
+    pub fn set_max_frame_length(&mut self, max_frame_length: usize) {
+        self.framing.set_max_frame_length(max_frame_length);
+    }
+
+    /// Returns the maximum length of a frame once decompressed.
+    pub fn max_decompressed_length(&self) -> usize {
+        self.max_decompressed_length
+    }
+
+    /// Sets the maximum length of a frame once decompressed. A frame that
+    /// inflates to more than this is an error of kind
+    /// `ErrorKind::InvalidData`, found without inflating the rest of it.
+    pub fn set_max_decompressed_length(&mut self, max_decompressed_length: usize) {
+        self.max_decompressed_length = max_decompressed_length;
+    }
+
+    /// Returns a reference to the inner codec.
+    pub fn get_ref(&self) -> &C {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner codec.
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+
+    /// Consumes the `CompressedCodec`, returning the inner codec.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+fn corrupt(e: io::Error) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("corrupt compressed frame: {e}"),
+    )
+}
+
+// Collects inflated bytes, failing once there are more than `limit`
+struct LimitedWriter<'a> {
+    buf: &'a mut BytesMut,
+    limit: usize,
+    exceeded: bool,
+}
+
+impl Write for LimitedWriter<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if data.len() > self.limit - self.buf.len() {
+            self.exceeded = true;
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "decompressed frame too long",
+            ));
+        }
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<C: Decoder> Decoder for CompressedCodec<C> {
+    type Item = C::Item;
+    type Error = C::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let compressed = match self.framing.decode(src)? {
+            Some(compressed) => compressed,
+            None => return Ok(None),
+        };
+
+        self.scratch.clear();
+        let mut inflater = DeflateDecoder::new(LimitedWriter {
+            buf: &mut self.scratch,
+            limit: self.max_decompressed_length,
+            exceeded: false,
+        });
+        let inflated = inflater
+            .write_all(&compressed)
+            .and_then(|_| inflater.try_finish());
+        if let Err(e) = inflated {
+            if inflater.get_ref().exceeded {
+                return Err(e.into());
+            }
+            return Err(corrupt(e).into());
+        }
+        drop(inflater);
+
+        decode_whole(&mut self.inner, &mut self.scratch).map(Some)
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        self.framing.expected_len(src)
+    }
+}
+
+impl<I, C: Encoder<I>> Encoder<I> for CompressedCodec<C> {
+    type Error = C::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.scratch.clear();
+        self.inner.encode(item, &mut self.scratch)?;
+
+        let mut compressed = BytesMut::with_capacity(self.scratch.len() / 2);
+        let mut deflater = DeflateEncoder::new((&mut compressed).writer(), self.level);
+        deflater.write_all(&self.scratch)?;
+        deflater.finish()?;
+
+        self.framing.encode(compressed, dst)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{BytesCodec, LinesCodec};
+    use crate::{FramedRead, FramedReader, FramedWrite, FramedWriter};
+
+    #[test]
+    fn round_trip() {
+        let mut framed = FramedWrite::new(Vec::new(), CompressedCodec::new(LinesCodec::new()));
+        let long = "z".repeat(1000);
+        framed.framed_write("hello").unwrap();
+        framed.framed_write(long.as_str()).unwrap();
+        let wire = framed.into_inner();
+        assert!(wire.len() < 1000);
+
+        let mut framed = FramedRead::new(&wire[..], CompressedCodec::new(LinesCodec::new()));
+        assert_eq!(framed.framed_read().unwrap(), "hello");
+        assert_eq!(framed.framed_read().unwrap(), long);
+        assert!(framed.framed_read().is_err());
+    }
+
+    #[test]
+    fn round_trip_across_reads() {
+        let payload = BytesMut::from(&b"compress me, compress me, compress me"[..]);
+        let mut codec = CompressedCodec::with_level(BytesCodec::new(), 9);
+        let mut wire = BytesMut::new();
+        codec.encode(payload.clone(), &mut wire).unwrap();
+        codec.encode(payload.clone(), &mut wire).unwrap();
+
+        let mut framed = FramedRead::with_capacity(&wire[..], codec, 3);
+        assert_eq!(framed.framed_read().unwrap(), payload);
+        assert_eq!(framed.framed_read().unwrap(), payload);
+    }
+
+    #[test]
+    fn decompressed_length_limited() {
+        let payload = BytesMut::from(&[0u8; 100_000][..]);
+        let mut wire = BytesMut::new();
+        CompressedCodec::new(BytesCodec::new())
+            .encode(payload.clone(), &mut wire)
+            .unwrap();
+        assert!(wire.len() < 1000);
+
+        let mut codec = CompressedCodec::new(BytesCodec::new());
+        codec.set_max_decompressed_length(99_999);
+        let err = codec.decode(&mut wire.clone()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "decompressed frame too long");
+
+        codec.set_max_decompressed_length(100_000);
+        assert_eq!(codec.decode(&mut wire).unwrap().unwrap(), payload);
+    }
+
+    #[test]
+    fn corrupt_payload() {
+        let mut codec = CompressedCodec::new(BytesCodec::new());
+        let mut wire = BytesMut::from(&[0, 0, 0, 4, 0xff, 0xff, 0xff, 0xff][..]);
+        let err = codec.decode(&mut wire).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn incomplete_inner_frame() {
+        let mut codec = CompressedCodec::new(LengthDelimitedCodec::new());
+        let mut inner = BytesMut::new();
+        // A length header promising more bytes than follow
+        inner.put_slice(&[0, 0, 0, 9, 1, 2]);
+        let mut wire = BytesMut::new();
+        CompressedCodec::new(BytesCodec::new())
+            .encode(inner, &mut wire)
+            .unwrap();
+        let err = codec.decode(&mut wire).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
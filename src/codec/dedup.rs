@@ -0,0 +1,160 @@
+//! A decoder adapter that drops repeats of the frame before.
+//!
+//! Telemetry streams often send the same reading over and over while
+//! nothing changes. Wrapping their codec in [`Dedup`] passes on only the
+//! frames that differ from the one before, so the application sees each
+//! change once.
+
+use bytes::BytesMut;
+
+use crate::{Decoder, Encoder};
+
+/// Wraps a decoder so that a frame equal to the one decoded just before it
+/// is skipped.
+///
+/// A repeated frame is still consumed from the buffer, and decoding carries
+/// on with the frames after it. So a read only returns once a frame that
+/// differs from the last one arrives: should the peer keep sending the same
+/// frame, `framed_read` blocks for as long as it does, and frames still in
+/// the buffer at EOF that are all repeats give a clean end of stream as if
+/// they had never been sent. Use a timeout or deadline read if a steady
+/// value must not hold up the caller indefinitely.
+///
+/// Only consecutive frames are compared, so `[A, A, B, A]` yields
+/// `[A, B, A]`. Encoding is passed straight through to the inner codec.
+#[derive(Debug, Clone)]
+pub struct Dedup<D: Decoder> {
+    inner: D,
+    last: Option<D::Item>,
+}
+
+impl<D: Decoder> Dedup<D> {
+    pub fn new(inner: D) -> Self {
+        Self { inner, last: None }
+    }
+
+    /// Returns the frame most recently passed on, if any.
+    pub fn last(&self) -> Option<&D::Item> {
+        self.last.as_ref()
+    }
+
+    /// Forget the last frame, so that the next frame is passed on even if
+    /// it repeats it. Useful after reconnecting to a peer.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// Returns a reference to the inner codec.
+    pub fn get_ref(&self) -> &D {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner codec.
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.inner
+    }
+
+    /// Consumes the `Dedup`, returning the inner codec.
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+}
+
+impl<D> Dedup<D>
+where
+    D: Decoder,
+    D::Item: PartialEq + Clone,
+{
+    /// Decode until a frame differs from the last one or no complete frame
+    /// is left, with `decode_eof` when `eof` is set.
+    fn skip_repeats(&mut self, src: &mut BytesMut, eof: bool) -> Result<Option<D::Item>, D::Error> {
+        loop {
+            let item = if eof {
+                self.inner.decode_eof(src)?
+            } else {
+                self.inner.decode(src)?
+            };
+            let Some(item) = item else {
+                return Ok(None);
+            };
+            if self.last.as_ref() != Some(&item) {
+                self.last = Some(item.clone());
+                return Ok(Some(item));
+            }
+        }
+    }
+}
+
+impl<D> Decoder for Dedup<D>
+where
+    D: Decoder,
+    D::Item: PartialEq + Clone,
+{
+    type Item = D::Item;
+    type Error = D::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.skip_repeats(src, false)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.skip_repeats(src, true)
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        self.inner.expected_len(src)
+    }
+
+    fn capacity_hint(&self) -> Option<usize> {
+        self.inner.capacity_hint()
+    }
+}
+
+impl<D: Decoder + Encoder<I>, I> Encoder<I> for Dedup<D> {
+    type Error = <D as Encoder<I>>::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.inner.encode(item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &I, dst: &mut BytesMut) -> Result<(), Self::Error>
+    where
+        I: Clone,
+    {
+        self.inner.encode_ref(item, dst)
+    }
+
+    fn size_hint(&self, item: &I) -> Option<usize> {
+        self.inner.size_hint(item)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::codec::LinesCodec;
+    use crate::{FramedRead, FramedReader};
+
+    #[test]
+    fn consecutive_repeats_dropped() {
+        let mut codec = Dedup::new(LinesCodec::new());
+        let mut src = BytesMut::from(&b"A\nA\nB\n"[..]);
+        assert_eq!(codec.decode_ready(&mut src).unwrap(), ["A", "B"]);
+        assert_eq!(codec.last().unwrap(), "B");
+
+        // Once forgotten the last frame may be repeated
+        codec.reset();
+        src.extend_from_slice(b"B\n");
+        assert_eq!(codec.decode(&mut src).unwrap().unwrap(), "B");
+    }
+
+    #[test]
+    fn repeats_across_reads() {
+        let wire = b"A\nA\nB\nB\nA\nA\n";
+        let mut framed = FramedRead::with_capacity(&wire[..], Dedup::new(LinesCodec::new()), 3);
+        assert_eq!(framed.framed_read().unwrap(), "A");
+        assert_eq!(framed.framed_read().unwrap(), "B");
+        assert_eq!(framed.framed_read().unwrap(), "A");
+        assert_eq!(framed.framed_read_opt().unwrap(), None);
+    }
+}
@@ -0,0 +1,214 @@
+//! A codec for frames terminated by an arbitrary byte sequence.
+
+use alloc::vec::Vec;
+use bytes::{Buf, BufMut, BytesMut};
+
+use crate::io::{self, Error, ErrorKind};
+use crate::{Decoder, Encoder};
+
+/// Splits a byte stream into frames ending with a delimiter, which may be a
+/// single byte such as `0x04` or a sequence such as `\r\n\r\n`.
+///
+/// By default decoded frames do not include the delimiter; use
+/// [`DelimiterCodec::set_include_delimiter`] to keep it. When encoding, the
+/// delimiter is appended to each frame. It is up to the caller to make sure
+/// a frame does not itself contain the delimiter.
+#[derive(Debug, Clone)]
+pub struct DelimiterCodec {
+    delimiter: Vec<u8>,
+    // Where to resume searching for the delimiter on the next call to `decode`
+    next_index: usize,
+    max_frame_length: usize,
+    include_delimiter: bool,
+}
+
+impl DelimiterCodec {
+    /// Create a `DelimiterCodec` with no limit on frame length.
+    ///
+    /// A peer that never sends the delimiter can make the read buffer grow
+    /// without limit, so for untrusted peers prefer
+    /// [`DelimiterCodec::new_with_max_length`].
+    ///
+    /// # Panics
+    /// If `delimiter` is empty.
+    pub fn new(delimiter: impl Into<Vec<u8>>) -> Self {
+        let delimiter = delimiter.into();
+        assert!(!delimiter.is_empty(), "delimiter must not be empty");
+        Self {
+            delimiter,
+            next_index: 0,
+            max_frame_length: usize::MAX,
+            include_delimiter: false,
+        }
+    }
+
+    /// Create a `DelimiterCodec` that returns an error of kind
+    /// `ErrorKind::InvalidData` if a frame is longer than `max_frame_length`
+    /// bytes, not counting the delimiter.
+    ///
+    /// # Panics
+    /// If `delimiter` is empty.
+    pub fn new_with_max_length(delimiter: impl Into<Vec<u8>>, max_frame_length: usize) -> Self {
+        Self {
+            max_frame_length,
+            ..Self::new(delimiter)
+        }
+    }
+
+    /// Returns the delimiter.
+    pub fn delimiter(&self) -> &[u8] {
+        &self.delimiter
+    }
+
+    /// Returns the maximum frame length.
+    pub fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+
+    /// Sets whether decoded frames end with the delimiter. Defaults to `false`.
+    pub fn set_include_delimiter(&mut self, include_delimiter: bool) {
+        self.include_delimiter = include_delimiter;
+    }
+}
+
+fn frame_too_long() -> io::Error {
+    Error::new(ErrorKind::InvalidData, "frame exceeds maximum frame length")
+}
+
+impl Decoder for DelimiterCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let delimiter_len = self.delimiter.len();
+        let start = self.next_index.min(src.len());
+        let found = src[start..]
+            .windows(delimiter_len)
+            .position(|window| window == self.delimiter);
+        match found {
+            Some(offset) => {
+                let end = start + offset;
+                self.next_index = 0;
+                if end > self.max_frame_length {
+                    src.advance(end + delimiter_len);
+                    return Err(frame_too_long());
+                }
+                if self.include_delimiter {
+                    Ok(Some(src.split_to(end + delimiter_len)))
+                } else {
+                    let frame = src.split_to(end);
+                    src.advance(delimiter_len);
+                    Ok(Some(frame))
+                }
+            }
+            None => {
+                // The delimiter may have started to arrive at the very end
+                let searched = src.len().saturating_sub(delimiter_len - 1);
+                self.next_index = searched;
+                if searched > self.max_frame_length {
+                    return Err(frame_too_long());
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Encoder<BytesMut> for DelimiterCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: BytesMut, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_ref(&item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &BytesMut, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if item.len() > self.max_frame_length {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "frame exceeds maximum frame length",
+            ));
+        }
+        dst.reserve(item.len() + self.delimiter.len());
+        dst.put_slice(item);
+        dst.put_slice(&self.delimiter);
+        Ok(())
+    }
+
+    fn size_hint(&self, item: &BytesMut) -> Option<usize> {
+        Some(item.len() + self.delimiter.len())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{FramedRead, FramedReader};
+
+    #[test]
+    fn single_byte_delimiter() {
+        let mut codec = DelimiterCodec::new([0x04]);
+        let mut src = BytesMut::from(&b"abc\x04\x04de"[..]);
+        assert_eq!(&codec.decode(&mut src).unwrap().unwrap()[..], b"abc");
+        assert_eq!(&codec.decode(&mut src).unwrap().unwrap()[..], b"");
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        assert_eq!(&src[..], b"de");
+    }
+
+    #[test]
+    fn delimiter_across_reads() {
+        let r = b"GET / HTTP/1.1\r\nHost: x\r\n\r\nsecond\r\n\r\n";
+        // Every split point falls somewhere different in the delimiter
+        for capacity in 1..8 {
+            let codec = DelimiterCodec::new("\r\n\r\n");
+            let mut framed = FramedRead::with_capacity(&r[..], codec, capacity);
+            assert_eq!(
+                &framed.framed_read().unwrap()[..],
+                b"GET / HTTP/1.1\r\nHost: x"
+            );
+            assert_eq!(&framed.framed_read().unwrap()[..], b"second");
+            assert!(framed.framed_read().is_err());
+        }
+    }
+
+    #[test]
+    fn include_delimiter() {
+        let mut codec = DelimiterCodec::new("||");
+        codec.set_include_delimiter(true);
+        let mut src = BytesMut::from(&b"a|b||c"[..]);
+        assert_eq!(&codec.decode(&mut src).unwrap().unwrap()[..], b"a|b||");
+        assert_eq!(&src[..], b"c");
+    }
+
+    #[test]
+    fn max_frame_length() {
+        let mut codec = DelimiterCodec::new_with_max_length("--", 4);
+        let mut src = BytesMut::from(&b"four--"[..]);
+        assert_eq!(&codec.decode(&mut src).unwrap().unwrap()[..], b"four");
+
+        // A partial delimiter at the end does not count against the limit
+        src.extend_from_slice(b"abcd-");
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        src.extend_from_slice(b"-");
+        assert_eq!(&codec.decode(&mut src).unwrap().unwrap()[..], b"abcd");
+
+        let mut src = BytesMut::from(&b"abcde--"[..]);
+        let err = codec.decode(&mut src).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(src.is_empty());
+
+        let mut src = BytesMut::from(&b"sixsix"[..]);
+        assert!(codec.decode(&mut src).is_err());
+    }
+
+    #[test]
+    fn encode_frames() {
+        let mut codec = DelimiterCodec::new_with_max_length("\r\n\r\n", 3);
+        let mut dst = BytesMut::new();
+        codec.encode(BytesMut::from(&b"hi"[..]), &mut dst).unwrap();
+        assert_eq!(&dst[..], b"hi\r\n\r\n");
+        let err = codec
+            .encode(BytesMut::from(&b"long"[..]), &mut dst)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,103 @@
+//! A codec for streams of fixed-size records.
+
+use bytes::BytesMut;
+
+use crate::io::{self, Error, ErrorKind};
+use crate::{Decoder, Encoder};
+
+/// Splits a stream into records of exactly `record_len` bytes.
+///
+/// Encoding checks that each item is exactly `record_len` bytes long and
+/// returns an error of kind `ErrorKind::InvalidInput` if it is not.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedLengthCodec {
+    record_len: usize,
+}
+
+impl FixedLengthCodec {
+    /// # Panics
+    /// If `record_len` is zero.
+    pub fn new(record_len: usize) -> Self {
+        assert!(record_len > 0, "record length must be greater than zero");
+        Self { record_len }
+    }
+
+    /// Returns the length of each record.
+    pub fn record_len(&self) -> usize {
+        self.record_len
+    }
+}
+
+impl Decoder for FixedLengthCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < self.record_len {
+            src.reserve(self.record_len - src.len());
+            return Ok(None);
+        }
+        Ok(Some(src.split_to(self.record_len)))
+    }
+}
+
+impl Encoder<BytesMut> for FixedLengthCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: BytesMut, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_ref(&item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &BytesMut, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if item.len() != self.record_len {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "record is not the expected length",
+            ));
+        }
+        dst.extend_from_slice(item);
+        Ok(())
+    }
+
+    fn size_hint(&self, _item: &BytesMut) -> Option<usize> {
+        Some(self.record_len)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{FramedRead, FramedReader};
+
+    #[test]
+    fn records_across_reads() {
+        let r: Vec<u8> = (0..12).collect();
+        let mut framed = FramedRead::with_capacity(&r[..], FixedLengthCodec::new(4), 3);
+        assert_eq!(&framed.framed_read().unwrap()[..], &[0, 1, 2, 3]);
+        assert_eq!(&framed.framed_read().unwrap()[..], &[4, 5, 6, 7]);
+        assert_eq!(&framed.framed_read().unwrap()[..], &[8, 9, 10, 11]);
+        assert!(framed.framed_read().is_err());
+    }
+
+    #[test]
+    fn partial_record() {
+        let mut codec = FixedLengthCodec::new(4);
+        let mut src = BytesMut::from(&[1u8, 2, 3][..]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        assert_eq!(src.len(), 3);
+    }
+
+    #[test]
+    fn encode_wrong_length() {
+        let mut codec = FixedLengthCodec::new(4);
+        let mut dst = BytesMut::new();
+        codec
+            .encode(BytesMut::from(&[1u8, 2, 3, 4][..]), &mut dst)
+            .unwrap();
+        let err = codec
+            .encode(BytesMut::from(&[1u8, 2, 3][..]), &mut dst)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(&dst[..], &[1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,217 @@
+//! Combinators for watching the frames that pass through a codec.
+//!
+//! [`DecoderExt::inspect`](crate::codec::DecoderExt::inspect) calls a
+//! function with each decoded item and
+//! [`EncoderExt::inspect_encoded`](crate::codec::EncoderExt::inspect_encoded)
+//! with each item along with the bytes it was encoded to. Neither changes
+//! the frames, which makes them suitable for logging and other diagnostics.
+//! [`crate::FramedRead::on_frame_decoded`] and
+//! [`crate::FramedWrite::on_frame_encoded`] add them to a framed reader or
+//! writer that already exists.
+
+use alloc::vec::Vec;
+use bytes::BytesMut;
+
+use crate::{Decoder, Encoder};
+
+/// A decoder that calls a function with each item it decodes.
+/// Returned by [`DecoderExt::inspect`](crate::codec::DecoderExt::inspect).
+///
+/// Encoding is passed straight through to the inner codec.
+#[derive(Debug, Clone)]
+pub struct InspectDecoder<D, F> {
+    pub(crate) inner: D,
+    pub(crate) f: F,
+}
+
+impl<D, F> InspectDecoder<D, F> {
+    /// Returns a reference to the inner codec.
+    pub fn get_ref(&self) -> &D {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner codec.
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.inner
+    }
+
+    /// Consumes the `InspectDecoder`, returning the inner codec.
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+}
+
+impl<D, F> Decoder for InspectDecoder<D, F>
+where
+    D: Decoder,
+    F: FnMut(&D::Item),
+{
+    type Item = D::Item;
+    type Error = D::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let item = self.inner.decode(src)?;
+        item.iter().for_each(&mut self.f);
+        Ok(item)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let item = self.inner.decode_eof(src)?;
+        item.iter().for_each(&mut self.f);
+        Ok(item)
+    }
+
+    fn decode_into(
+        &mut self,
+        src: &mut BytesMut,
+        dst: &mut Self::Item,
+    ) -> Result<bool, Self::Error> {
+        let decoded = self.inner.decode_into(src, dst)?;
+        if decoded {
+            (self.f)(dst);
+        }
+        Ok(decoded)
+    }
+
+    fn decode_ready(&mut self, src: &mut BytesMut) -> Result<Vec<Self::Item>, Self::Error> {
+        let items = self.inner.decode_ready(src)?;
+        items.iter().for_each(&mut self.f);
+        Ok(items)
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        self.inner.expected_len(src)
+    }
+
+    fn capacity_hint(&self) -> Option<usize> {
+        self.inner.capacity_hint()
+    }
+}
+
+impl<D: Encoder<I>, F, I> Encoder<I> for InspectDecoder<D, F> {
+    type Error = D::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.inner.encode(item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &I, dst: &mut BytesMut) -> Result<(), Self::Error>
+    where
+        I: Clone,
+    {
+        self.inner.encode_ref(item, dst)
+    }
+
+    fn size_hint(&self, item: &I) -> Option<usize> {
+        self.inner.size_hint(item)
+    }
+}
+
+/// An encoder that calls a function with each item it encodes and the
+/// bytes of the frame it produced.
+/// Returned by [`EncoderExt::inspect_encoded`](crate::codec::EncoderExt::inspect_encoded).
+///
+/// Items are encoded with [`Encoder::encode_ref`] so that they can still be
+/// handed to the function afterwards, which is why they must be `Clone`.
+/// Decoding is passed straight through to the inner codec.
+#[derive(Debug, Clone)]
+pub struct InspectEncoder<E, G> {
+    pub(crate) inner: E,
+    pub(crate) g: G,
+}
+
+impl<E, G> InspectEncoder<E, G> {
+    /// Returns a reference to the inner codec.
+    pub fn get_ref(&self) -> &E {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner codec.
+    pub fn get_mut(&mut self) -> &mut E {
+        &mut self.inner
+    }
+
+    /// Consumes the `InspectEncoder`, returning the inner codec.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E, G, I> Encoder<I> for InspectEncoder<E, G>
+where
+    E: Encoder<I>,
+    G: FnMut(&I, &[u8]),
+    I: Clone,
+{
+    type Error = E::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_ref(&item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &I, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let start = dst.len();
+        self.inner.encode_ref(item, dst)?;
+        (self.g)(item, &dst[start..]);
+        Ok(())
+    }
+
+    fn size_hint(&self, item: &I) -> Option<usize> {
+        self.inner.size_hint(item)
+    }
+}
+
+impl<E: Decoder, G> Decoder for InspectEncoder<E, G> {
+    type Item = E::Item;
+    type Error = E::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.inner.decode(src)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.inner.decode_eof(src)
+    }
+
+    fn decode_into(
+        &mut self,
+        src: &mut BytesMut,
+        dst: &mut Self::Item,
+    ) -> Result<bool, Self::Error> {
+        self.inner.decode_into(src, dst)
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        self.inner.expected_len(src)
+    }
+
+    fn capacity_hint(&self) -> Option<usize> {
+        self.inner.capacity_hint()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::codec::{DecoderExt, EncoderExt, LinesCodec};
+    use crate::{FramedRead, FramedReader, FramedWrite, FramedWriter};
+    use std::cell::RefCell;
+
+    #[test]
+    fn inspect_both_ways() {
+        let seen = RefCell::new(vec![]);
+        let codec = LinesCodec::new()
+            .inspect(|line: &String| seen.borrow_mut().push(format!("in {line}")))
+            .inspect_encoded(|line: &&str, wire: &[u8]| {
+                seen.borrow_mut().push(format!("out {line} {wire:?}"))
+            });
+
+        let mut writer = FramedWrite::new(vec![], codec);
+        writer.framed_write("hi").unwrap();
+        let codec = writer.encoder().clone();
+        let wire = writer.into_inner();
+
+        let mut reader = FramedRead::new(&wire[..], codec);
+        assert_eq!(reader.framed_read().unwrap(), "hi");
+        assert_eq!(*seen.borrow(), ["out hi [104, 105, 10]", "in hi"]);
+    }
+}
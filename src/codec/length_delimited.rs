@@ -0,0 +1,385 @@
+//! A codec for frames that are preceded by a length header.
+//!
+//! Each frame on the wire is a header holding the payload length as an
+//! unsigned integer, followed by that many bytes of payload:
+//!
+//! ```text
+//! +---------------+---------------------+
+//! | length header |       payload       |
+//! +---------------+---------------------+
+//! ```
+//!
+//! By default the header is a big-endian `u32` that counts only the
+//! payload. Use [`LengthDelimitedCodec::builder`] to change this.
+
+use alloc::vec::Vec;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::io::{self, Error, ErrorKind};
+use crate::{Decoder, Encoder};
+
+const DEFAULT_MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+/// Decodes and encodes length prefixed frames.
+///
+/// Decoded items are the payload bytes without the header.
+#[derive(Debug, Clone)]
+pub struct LengthDelimitedCodec {
+    builder: Builder,
+}
+
+impl LengthDelimitedCodec {
+    /// Create a codec using a big-endian `u32` header and an 8 MiB
+    /// maximum frame length.
+    pub fn new() -> Self {
+        Self {
+            builder: Builder::new(),
+        }
+    }
+
+    /// Returns a [`Builder`] for configuring the codec.
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    /// Returns the maximum payload length that will be decoded or encoded.
+    pub fn max_frame_length(&self) -> usize {
+        self.builder.max_frame_length
+    }
+
+    /// Sets the maximum payload length that will be decoded or encoded.
+    ///
+    /// A header declaring a longer payload is rejected as soon as it has
+    /// been read, before any of the payload is buffered.
+    pub fn set_max_frame_length(&mut self, val: usize) {
+        self.builder.max_frame_length = val;
+    }
+
+    fn header_len(&self) -> usize {
+        self.builder.length_field_length
+    }
+
+    fn max_header_value(&self) -> u64 {
+        match self.header_len() {
+            8 => u64::MAX,
+            n => (1u64 << (n * 8)) - 1,
+        }
+    }
+}
+
+impl Default for LengthDelimitedCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for LengthDelimitedCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(frame_len) = self.frame_len(src)? else {
+            return Ok(None);
+        };
+        if src.len() < frame_len {
+            src.reserve(frame_len - src.len());
+            return Ok(None);
+        }
+
+        let header_len = self.header_len();
+        src.advance(header_len);
+        Ok(Some(src.split_to(frame_len - header_len)))
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        self.frame_len(src).ok().flatten()
+    }
+}
+
+impl LengthDelimitedCodec {
+    /// Read the header at the start of `src`, returning the length of the
+    /// whole frame including the header, or `None` if the header is not
+    /// complete yet.
+    fn frame_len(&self, src: &BytesMut) -> io::Result<Option<usize>> {
+        let header_len = self.header_len();
+        if src.len() < header_len {
+            return Ok(None);
+        }
+
+        let mut header = &src[..header_len];
+        let value = if self.builder.little_endian {
+            header.get_uint_le(header_len)
+        } else {
+            header.get_uint(header_len)
+        };
+
+        let payload_len = if self.builder.length_includes_header {
+            value.checked_sub(header_len as u64).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    "frame length is shorter than its header",
+                )
+            })?
+        } else {
+            value
+        };
+
+        if payload_len > self.builder.max_frame_length as u64 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "frame exceeds maximum frame length",
+            ));
+        }
+
+        Ok(Some(header_len + payload_len as usize))
+    }
+}
+
+impl Encoder<BytesMut> for LengthDelimitedCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: BytesMut, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_ref(&item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &BytesMut, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.reserve(self.header_len() + item.len());
+        self.put_header(item.len(), dst)?;
+        dst.extend_from_slice(item);
+        Ok(())
+    }
+
+    /// Writes the header and hands over the payload as it is, without
+    /// copying it.
+    fn encode_vectored(
+        &mut self,
+        item: BytesMut,
+        bufs: &mut Vec<Bytes>,
+    ) -> Result<(), Self::Error> {
+        let mut header = BytesMut::with_capacity(self.header_len());
+        self.put_header(item.len(), &mut header)?;
+        bufs.push(header.freeze());
+        bufs.push(item.freeze());
+        Ok(())
+    }
+
+    fn size_hint(&self, item: &BytesMut) -> Option<usize> {
+        Some(self.header_len() + item.len())
+    }
+}
+
+impl LengthDelimitedCodec {
+    /// Append the header for a payload of `payload_len` bytes to `dst`.
+    fn put_header(&self, payload_len: usize, dst: &mut BytesMut) -> io::Result<()> {
+        let header_len = self.header_len();
+        if payload_len > self.builder.max_frame_length {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "frame exceeds maximum frame length",
+            ));
+        }
+
+        let mut value = payload_len as u64;
+        if self.builder.length_includes_header {
+            value += header_len as u64;
+        }
+        if value > self.max_header_value() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "frame length does not fit in the length header",
+            ));
+        }
+
+        if self.builder.little_endian {
+            dst.put_uint_le(value, header_len);
+        } else {
+            dst.put_uint(value, header_len);
+        }
+        Ok(())
+    }
+}
+
+/// Configures a [`LengthDelimitedCodec`].
+#[derive(Debug, Clone, Copy)]
+pub struct Builder {
+    length_field_length: usize,
+    little_endian: bool,
+    length_includes_header: bool,
+    max_frame_length: usize,
+}
+
+impl Builder {
+    /// Create a builder with the default settings: a big-endian `u32`
+    /// header that does not count itself and an 8 MiB maximum frame length.
+    pub fn new() -> Self {
+        Self {
+            length_field_length: 4,
+            little_endian: false,
+            length_includes_header: false,
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+        }
+    }
+
+    /// Sets the width of the length header in bytes.
+    /// Widths of 1, 2, 4 and 8 correspond to `u8`, `u16`, `u32` and `u64`.
+    ///
+    /// # Panics
+    /// If `val` is not in the range `1..=8`.
+    pub fn length_field_length(&mut self, val: usize) -> &mut Self {
+        assert!(
+            (1..=8).contains(&val),
+            "length field length must be between 1 and 8 bytes"
+        );
+        self.length_field_length = val;
+        self
+    }
+
+    /// Read and write the length header as big-endian. This is the default.
+    pub fn big_endian(&mut self) -> &mut Self {
+        self.little_endian = false;
+        self
+    }
+
+    /// Read and write the length header as little-endian.
+    pub fn little_endian(&mut self) -> &mut Self {
+        self.little_endian = true;
+        self
+    }
+
+    /// Sets whether the value in the length header counts the header itself
+    /// as well as the payload.
+    pub fn length_includes_header(&mut self, val: bool) -> &mut Self {
+        self.length_includes_header = val;
+        self
+    }
+
+    /// Sets the maximum payload length. Decoding a longer frame returns an
+    /// error of kind `ErrorKind::InvalidData` as soon as its header has been
+    /// read, so a hostile header cannot make the read buffer grow.
+    pub fn max_frame_length(&mut self, val: usize) -> &mut Self {
+        self.max_frame_length = val;
+        self
+    }
+
+    /// Create a [`LengthDelimitedCodec`] with the current settings.
+    pub fn new_codec(&self) -> LengthDelimitedCodec {
+        LengthDelimitedCodec { builder: *self }
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(codec: &mut LengthDelimitedCodec, payload: &[u8], wire: &[u8]) {
+        let mut dst = BytesMut::new();
+        let item = BytesMut::from(payload);
+        assert_eq!(codec.size_hint(&item), Some(wire.len()));
+        codec.encode(item, &mut dst).unwrap();
+        assert_eq!(&dst[..], wire);
+
+        let mut src = BytesMut::from(wire);
+        for i in 0..wire.len() {
+            let mut partial = BytesMut::from(&wire[..i]);
+            assert!(codec.decode(&mut partial).unwrap().is_none());
+        }
+        assert_eq!(&codec.decode(&mut src).unwrap().unwrap()[..], payload);
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn u8_header() {
+        let mut codec = LengthDelimitedCodec::builder()
+            .length_field_length(1)
+            .new_codec();
+        round_trip(&mut codec, b"hi", &[2, b'h', b'i']);
+    }
+
+    #[test]
+    fn u16_header() {
+        let mut codec = LengthDelimitedCodec::builder()
+            .length_field_length(2)
+            .new_codec();
+        round_trip(&mut codec, b"hi", &[0, 2, b'h', b'i']);
+    }
+
+    #[test]
+    fn u16_little_endian_header() {
+        let mut codec = LengthDelimitedCodec::builder()
+            .length_field_length(2)
+            .little_endian()
+            .new_codec();
+        round_trip(&mut codec, b"hi", &[2, 0, b'h', b'i']);
+    }
+
+    #[test]
+    fn u32_header() {
+        let mut codec = LengthDelimitedCodec::new();
+        round_trip(&mut codec, b"hi", &[0, 0, 0, 2, b'h', b'i']);
+    }
+
+    #[test]
+    fn u64_header() {
+        let mut codec = LengthDelimitedCodec::builder()
+            .length_field_length(8)
+            .new_codec();
+        round_trip(&mut codec, b"hi", &[0, 0, 0, 0, 0, 0, 0, 2, b'h', b'i']);
+    }
+
+    #[test]
+    fn length_includes_header() {
+        let mut codec = LengthDelimitedCodec::builder()
+            .length_field_length(2)
+            .length_includes_header(true)
+            .new_codec();
+        round_trip(&mut codec, b"hi", &[0, 4, b'h', b'i']);
+
+        let mut src = BytesMut::from(&[0u8, 1][..]);
+        let err = codec.decode(&mut src).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn decode_too_large_frame() {
+        let mut codec = LengthDelimitedCodec::builder()
+            .length_field_length(1)
+            .max_frame_length(4)
+            .new_codec();
+        let mut src = BytesMut::from(&[5u8, 1, 2, 3, 4, 5][..]);
+        let err = codec.decode(&mut src).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reject_huge_length_from_header_alone() {
+        let mut codec = LengthDelimitedCodec::builder()
+            .length_field_length(8)
+            .max_frame_length(16)
+            .new_codec();
+        let mut src = BytesMut::from(&u64::MAX.to_be_bytes()[..]);
+        let capacity = src.capacity();
+        let err = codec.decode(&mut src).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(src.capacity(), capacity);
+        assert_eq!(codec.expected_len(&src), None);
+    }
+
+    #[test]
+    fn encode_too_large_frame() {
+        let mut codec = LengthDelimitedCodec::builder()
+            .length_field_length(1)
+            .new_codec();
+        let mut dst = BytesMut::new();
+        let err = codec
+            .encode(BytesMut::from(&[0u8; 256][..]), &mut dst)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(dst.is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+//! A decoder adapter that caps the number of frames a stream may carry.
+//!
+//! A server can bound the work a single connection makes it do by wrapping
+//! the connection's codec in [`Limited`]. Once the limit has been reached,
+//! any further bytes from the peer are an error of kind
+//! `ErrorKind::InvalidData`, which leaves the `FramedRead` poisoned.
+
+use bytes::BytesMut;
+
+use crate::io::{Error, ErrorKind};
+use crate::{Decoder, Encoder};
+
+/// Wraps a codec so that at most `max_frames` frames are decoded.
+///
+/// After that many frames, a decode with bytes in the buffer fails instead
+/// of decoding another one. An empty buffer is not an error, so a peer
+/// that sends exactly `max_frames` frames and then closes the connection
+/// ends the stream cleanly. Encoding is passed straight through to the
+/// inner codec.
+#[derive(Debug, Clone)]
+pub struct Limited<D> {
+    inner: D,
+    max_frames: usize,
+    frames: usize,
+}
+
+impl<D> Limited<D> {
+    pub fn new(inner: D, max_frames: usize) -> Self {
+        Self {
+            inner,
+            max_frames,
+            frames: 0,
+        }
+    }
+
+    /// Returns the most frames that may be decoded.
+    pub fn max_frames(&self) -> usize {
+        self.max_frames
+    }
+
+    /// Returns the number of frames decoded so far.
+    pub fn frames_decoded(&self) -> usize {
+        self.frames
+    }
+
+    /// Returns a reference to the inner codec.
+    pub fn get_ref(&self) -> &D {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner codec.
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.inner
+    }
+
+    /// Consumes the `Limited`, returning the inner codec.
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+
+    fn check_limit(&self, src: &BytesMut) -> Result<(), Error> {
+        if self.frames >= self.max_frames && !src.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidData, "frame limit reached"));
+        }
+        Ok(())
+    }
+
+    fn count<T>(&mut self, item: Option<T>) -> Option<T> {
+        if item.is_some() {
+            self.frames += 1;
+        }
+        item
+    }
+}
+
+impl<D: Decoder> Decoder for Limited<D> {
+    type Item = D::Item;
+    type Error = D::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.check_limit(src)?;
+        let item = self.inner.decode(src)?;
+        Ok(self.count(item))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.check_limit(src)?;
+        let item = self.inner.decode_eof(src)?;
+        Ok(self.count(item))
+    }
+
+    fn decode_into(
+        &mut self,
+        src: &mut BytesMut,
+        dst: &mut Self::Item,
+    ) -> Result<bool, Self::Error> {
+        self.check_limit(src)?;
+        let decoded = self.inner.decode_into(src, dst)?;
+        if decoded {
+            self.frames += 1;
+        }
+        Ok(decoded)
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        self.inner.expected_len(src)
+    }
+
+    fn capacity_hint(&self) -> Option<usize> {
+        self.inner.capacity_hint()
+    }
+}
+
+impl<D: Encoder<I>, I> Encoder<I> for Limited<D> {
+    type Error = D::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.inner.encode(item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &I, dst: &mut BytesMut) -> Result<(), Self::Error>
+    where
+        I: Clone,
+    {
+        self.inner.encode_ref(item, dst)
+    }
+
+    fn size_hint(&self, item: &I) -> Option<usize> {
+        self.inner.size_hint(item)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::codec::LengthDelimitedCodec;
+    use crate::{FramedRead, FramedReader, FramedWrite, FramedWriter};
+
+    fn wire(frames: usize) -> Vec<u8> {
+        let mut framed = FramedWrite::new(vec![], LengthDelimitedCodec::new());
+        for _ in 0..frames {
+            framed.framed_write(BytesMut::from(&b"hi"[..])).unwrap();
+        }
+        framed.into_inner()
+    }
+
+    #[test]
+    fn third_frame_over_limit() {
+        let wire = wire(3);
+        let codec = Limited::new(LengthDelimitedCodec::new(), 2);
+        let mut framed = FramedRead::with_capacity(&wire[..], codec, 4);
+        assert_eq!(&framed.framed_read().unwrap()[..], b"hi");
+        assert_eq!(&framed.framed_read().unwrap()[..], b"hi");
+        let err = framed.framed_read().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(framed.decoder().frames_decoded(), 2);
+    }
+
+    #[test]
+    fn clean_end_at_limit() {
+        let wire = wire(2);
+        let codec = Limited::new(LengthDelimitedCodec::new(), 2);
+        let mut framed = FramedRead::new(&wire[..], codec);
+        assert!(framed.framed_read().is_ok());
+        assert!(framed.framed_read().is_ok());
+        assert_eq!(framed.framed_read_opt().unwrap(), None);
+    }
+}
@@ -0,0 +1,231 @@
+//! A codec for newline delimited text.
+
+use alloc::string::String;
+use bytes::{Buf, BufMut, BytesMut};
+
+use crate::io::{self, Error, ErrorKind};
+use crate::{Decoder, Encoder};
+
+/// Splits a byte stream into lines of UTF-8 text.
+///
+/// Lines are terminated by `\n`. By default a `\r` immediately before the
+/// `\n` is also removed so that `\r\n` terminated protocols work too. The
+/// decoded `String` never contains the line ending. When encoding, a `\n`
+/// is appended to each line.
+///
+/// A line that is not valid UTF-8 is an error of kind
+/// `ErrorKind::InvalidData` unless [`LinesCodec::set_lossy_utf8`] is used.
+#[derive(Debug, Clone)]
+pub struct LinesCodec {
+    // Where to resume searching for a newline on the next call to `decode`
+    next_index: usize,
+    max_length: usize,
+    strip_cr: bool,
+    lossy_utf8: bool,
+}
+
+impl LinesCodec {
+    /// Create a `LinesCodec` with no limit on line length.
+    ///
+    /// A peer that never sends a newline can make the read buffer grow
+    /// without limit, so for untrusted peers prefer
+    /// [`LinesCodec::new_with_max_length`].
+    pub fn new() -> Self {
+        Self {
+            next_index: 0,
+            max_length: usize::MAX,
+            strip_cr: true,
+            lossy_utf8: false,
+        }
+    }
+
+    /// Create a `LinesCodec` that returns an error of kind
+    /// `ErrorKind::InvalidData` if a line is longer than `max_length` bytes,
+    /// not counting the line ending.
+    pub fn new_with_max_length(max_length: usize) -> Self {
+        Self {
+            max_length,
+            ..Self::new()
+        }
+    }
+
+    /// Returns the maximum line length.
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    /// Sets whether a `\r` before the `\n` is removed. Defaults to `true`.
+    pub fn set_strip_cr(&mut self, strip_cr: bool) {
+        self.strip_cr = strip_cr;
+    }
+
+    /// Sets whether invalid UTF-8 is replaced with `U+FFFD REPLACEMENT
+    /// CHARACTER` rather than being an error. Defaults to `false`.
+    ///
+    /// Useful for logs and other text where the odd garbled line should not
+    /// end the connection.
+    pub fn set_lossy_utf8(&mut self, lossy_utf8: bool) {
+        self.lossy_utf8 = lossy_utf8;
+    }
+
+    fn decode_line(&self, mut line: BytesMut) -> io::Result<String> {
+        if self.strip_cr && line.last() == Some(&b'\r') {
+            line.truncate(line.len() - 1);
+        }
+        if line.len() > self.max_length {
+            return Err(line_too_long());
+        }
+        if self.lossy_utf8 {
+            return Ok(String::from_utf8_lossy(&line).into_owned());
+        }
+        String::from_utf8(line.to_vec())
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "line is not valid UTF-8"))
+    }
+}
+
+impl Default for LinesCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn line_too_long() -> io::Error {
+    Error::new(ErrorKind::InvalidData, "line exceeds maximum length")
+}
+
+impl Decoder for LinesCodec {
+    type Item = String;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let start = self.next_index.min(src.len());
+        match src[start..].iter().position(|b| *b == b'\n') {
+            Some(offset) => {
+                let newline = start + offset;
+                self.next_index = 0;
+                let line = src.split_to(newline);
+                src.advance(1);
+                self.decode_line(line).map(Some)
+            }
+            None => {
+                self.next_index = src.len();
+                // Allow one extra byte for a `\r` that may be stripped
+                if src.len() > self.max_length.saturating_add(1) {
+                    return Err(line_too_long());
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode(src)? {
+            Some(line) => Ok(Some(line)),
+            None if src.is_empty() => Ok(None),
+            None => {
+                self.next_index = 0;
+                let line = src.split();
+                self.decode_line(line).map(Some)
+            }
+        }
+    }
+}
+
+fn encode_line(line: &str, dst: &mut BytesMut) {
+    dst.reserve(line.len() + 1);
+    dst.put_slice(line.as_bytes());
+    dst.put_u8(b'\n');
+}
+
+impl<T: AsRef<str>> Encoder<T> for LinesCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        encode_line(item.as_ref(), dst);
+        Ok(())
+    }
+
+    fn encode_ref(&mut self, item: &T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        encode_line(item.as_ref(), dst);
+        Ok(())
+    }
+
+    fn size_hint(&self, item: &T) -> Option<usize> {
+        Some(item.as_ref().len() + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_lines() {
+        let mut codec = LinesCodec::new();
+        let mut src = BytesMut::from("one\ntwo\r\nthr");
+        assert_eq!(codec.decode(&mut src).unwrap().unwrap(), "one");
+        assert_eq!(codec.decode(&mut src).unwrap().unwrap(), "two");
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        src.extend_from_slice(b"ee\n");
+        assert_eq!(codec.decode(&mut src).unwrap().unwrap(), "three");
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn keep_cr() {
+        let mut codec = LinesCodec::new();
+        codec.set_strip_cr(false);
+        let mut src = BytesMut::from("crlf\r\n");
+        assert_eq!(codec.decode(&mut src).unwrap().unwrap(), "crlf\r");
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let mut codec = LinesCodec::new();
+        let mut src = BytesMut::from(&b"bad \xff\xfe line\nok\n"[..]);
+        let err = codec.decode(&mut src).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(codec.decode(&mut src).unwrap().unwrap(), "ok");
+
+        let mut codec = LinesCodec::new();
+        codec.set_lossy_utf8(true);
+        let mut src = BytesMut::from(&b"bad \xff\xfe line\nok\n"[..]);
+        assert_eq!(
+            codec.decode(&mut src).unwrap().unwrap(),
+            "bad \u{fffd}\u{fffd} line"
+        );
+        assert_eq!(codec.decode(&mut src).unwrap().unwrap(), "ok");
+    }
+
+    #[test]
+    fn max_length() {
+        let mut codec = LinesCodec::new_with_max_length(4);
+        let mut src = BytesMut::from("four\r\nsix!!!");
+        assert_eq!(codec.decode(&mut src).unwrap().unwrap(), "four");
+        assert!(codec.decode(&mut src).is_err());
+
+        let mut codec = LinesCodec::new_with_max_length(4);
+        let mut src = BytesMut::from("five!\n");
+        let err = codec.decode(&mut src).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn trailing_line_on_eof() {
+        let mut codec = LinesCodec::new();
+        let mut src = BytesMut::from("done\nlast");
+        assert_eq!(codec.decode_eof(&mut src).unwrap().unwrap(), "done");
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        assert_eq!(codec.decode_eof(&mut src).unwrap().unwrap(), "last");
+        assert!(codec.decode_eof(&mut src).unwrap().is_none());
+    }
+
+    #[test]
+    fn encode_lines() {
+        let mut codec = LinesCodec::new();
+        let mut dst = BytesMut::new();
+        codec.encode("hello", &mut dst).unwrap();
+        codec.encode(String::from("world"), &mut dst).unwrap();
+        assert_eq!(&dst[..], b"hello\nworld\n");
+    }
+}
@@ -0,0 +1,300 @@
+//! Combinators for layering a transformation over an existing codec.
+//!
+//! [`DecoderExt::map`] converts each decoded item and [`EncoderExt::with`]
+//! converts each item before it is encoded. Together they let a generic
+//! framing codec, such as [`crate::codec::LengthDelimitedCodec`], carry a
+//! domain type without writing a new codec:
+//!
+//! ```
+//! # #[cfg(feature = "std")] {
+//! use bytes::BytesMut;
+//! use framous::codec::{DecoderExt, EncoderExt, LengthDelimitedCodec};
+//! use framous::{Framed, FramedReader, FramedWriter};
+//!
+//! let codec = LengthDelimitedCodec::new()
+//!     .map(|frame: BytesMut| String::from_utf8_lossy(&frame).into_owned())
+//!     .with(|text: String| BytesMut::from(text.as_bytes()));
+//!
+//! let mut wire = vec![];
+//! let mut framed = Framed::with_codec(&[0u8, 0, 0, 2, b'h', b'i'][..], &mut wire, codec);
+//! assert_eq!(framed.framed_read().unwrap(), "hi");
+//! framed.framed_write("ok".to_string()).unwrap();
+//! drop(framed);
+//! assert_eq!(wire, [0, 0, 0, 2, b'o', b'k']);
+//! # }
+//! ```
+
+use alloc::vec::Vec;
+use bytes::{Bytes, BytesMut};
+
+use crate::codec::inspect::{InspectDecoder, InspectEncoder};
+use crate::{Decoder, Encoder};
+
+/// A decoder that passes each item through a function.
+/// Returned by [`DecoderExt::map`].
+///
+/// Encoding is passed straight through to the inner codec.
+#[derive(Debug, Clone)]
+pub struct MappedDecoder<D, F> {
+    inner: D,
+    f: F,
+}
+
+impl<D, F> MappedDecoder<D, F> {
+    /// Returns a reference to the inner codec.
+    pub fn get_ref(&self) -> &D {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner codec.
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.inner
+    }
+
+    /// Consumes the `MappedDecoder`, returning the inner codec.
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+}
+
+impl<D, F, T> Decoder for MappedDecoder<D, F>
+where
+    D: Decoder,
+    F: FnMut(D::Item) -> T,
+{
+    type Item = T;
+    type Error = D::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.inner.decode(src)?.map(&mut self.f))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.inner.decode_eof(src)?.map(&mut self.f))
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        self.inner.expected_len(src)
+    }
+
+    fn capacity_hint(&self) -> Option<usize> {
+        self.inner.capacity_hint()
+    }
+}
+
+impl<D: Encoder<I>, F, I> Encoder<I> for MappedDecoder<D, F> {
+    type Error = D::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.inner.encode(item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &I, dst: &mut BytesMut) -> Result<(), Self::Error>
+    where
+        I: Clone,
+    {
+        self.inner.encode_ref(item, dst)
+    }
+
+    fn encode_vectored(&mut self, item: I, bufs: &mut Vec<Bytes>) -> Result<(), Self::Error> {
+        self.inner.encode_vectored(item, bufs)
+    }
+
+    fn size_hint(&self, item: &I) -> Option<usize> {
+        self.inner.size_hint(item)
+    }
+}
+
+/// An encoder that passes each item through a function before encoding it.
+/// Returned by [`EncoderExt::with`].
+///
+/// Decoding is passed straight through to the inner codec.
+#[derive(Debug, Clone)]
+pub struct MappedEncoder<E, G> {
+    inner: E,
+    g: G,
+}
+
+impl<E, G> MappedEncoder<E, G> {
+    /// Returns a reference to the inner codec.
+    pub fn get_ref(&self) -> &E {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner codec.
+    pub fn get_mut(&mut self) -> &mut E {
+        &mut self.inner
+    }
+
+    /// Consumes the `MappedEncoder`, returning the inner codec.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E, G, T, I> Encoder<T> for MappedEncoder<E, G>
+where
+    E: Encoder<I>,
+    G: FnMut(T) -> I,
+{
+    type Error = E::Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.inner.encode((self.g)(item), dst)
+    }
+
+    fn encode_vectored(&mut self, item: T, bufs: &mut Vec<Bytes>) -> Result<(), Self::Error> {
+        self.inner.encode_vectored((self.g)(item), bufs)
+    }
+}
+
+impl<E: Decoder, G> Decoder for MappedEncoder<E, G> {
+    type Item = E::Item;
+    type Error = E::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.inner.decode(src)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.inner.decode_eof(src)
+    }
+
+    fn decode_into(
+        &mut self,
+        src: &mut BytesMut,
+        dst: &mut Self::Item,
+    ) -> Result<bool, Self::Error> {
+        self.inner.decode_into(src, dst)
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        self.inner.expected_len(src)
+    }
+
+    fn capacity_hint(&self) -> Option<usize> {
+        self.inner.capacity_hint()
+    }
+}
+
+/// Adds [`DecoderExt::map`] and [`DecoderExt::inspect`] to every
+/// [`Decoder`].
+pub trait DecoderExt: Decoder + Sized {
+    /// Convert every decoded item with `f`.
+    fn map<F, T>(self, f: F) -> MappedDecoder<Self, F>
+    where
+        F: FnMut(Self::Item) -> T,
+    {
+        MappedDecoder { inner: self, f }
+    }
+
+    /// Call `f` with every decoded item, leaving the items unchanged.
+    fn inspect<F>(self, f: F) -> InspectDecoder<Self, F>
+    where
+        F: FnMut(&Self::Item),
+    {
+        InspectDecoder { inner: self, f }
+    }
+}
+
+impl<D: Decoder> DecoderExt for D {}
+
+/// Adds [`EncoderExt::with`] and [`EncoderExt::inspect_encoded`] to every
+/// [`Encoder`].
+pub trait EncoderExt<I>: Encoder<I> + Sized {
+    /// Convert every item with `g` before encoding it.
+    fn with<G, T>(self, g: G) -> MappedEncoder<Self, G>
+    where
+        G: FnMut(T) -> I,
+    {
+        MappedEncoder { inner: self, g }
+    }
+
+    /// Call `g` with every item once it has been encoded, along with the
+    /// bytes of its frame.
+    fn inspect_encoded<G>(self, g: G) -> InspectEncoder<Self, G>
+    where
+        G: FnMut(&I, &[u8]),
+        I: Clone,
+    {
+        InspectEncoder { inner: self, g }
+    }
+}
+
+impl<E: Encoder<I>, I> EncoderExt<I> for E {}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::codec::LengthDelimitedCodec;
+    use crate::{Framed, FramedRead, FramedReader, FramedWrite, FramedWriter};
+    use bytes::BufMut;
+
+    #[derive(Debug, PartialEq)]
+    enum Command {
+        Ping,
+        Say(String),
+        Unknown(u8),
+    }
+
+    fn parse(mut frame: BytesMut) -> Command {
+        match frame.first().copied() {
+            Some(0) => Command::Ping,
+            Some(1) => Command::Say(String::from_utf8_lossy(&frame.split_off(1)).into_owned()),
+            tag => Command::Unknown(tag.unwrap_or_default()),
+        }
+    }
+
+    fn serialize(cmd: Command) -> BytesMut {
+        let mut frame = BytesMut::new();
+        match cmd {
+            Command::Ping => frame.put_u8(0),
+            Command::Say(text) => {
+                frame.put_u8(1);
+                frame.put_slice(text.as_bytes());
+            }
+            Command::Unknown(tag) => frame.put_u8(tag),
+        }
+        frame
+    }
+
+    #[test]
+    fn map_decoded_frames() {
+        let r = [0u8, 0, 0, 1, 0, 0, 0, 0, 3, 1, b'h', b'i', 0, 0, 0, 1, 9];
+        let mut reader = FramedRead::new(&r[..], LengthDelimitedCodec::new().map(parse));
+        assert_eq!(reader.framed_read().unwrap(), Command::Ping);
+        assert_eq!(reader.framed_read().unwrap(), Command::Say("hi".into()));
+        assert_eq!(reader.framed_read().unwrap(), Command::Unknown(9));
+        assert!(reader.framed_read().is_err());
+    }
+
+    #[test]
+    fn map_items_before_encoding() {
+        let mut writer = FramedWrite::new(vec![], LengthDelimitedCodec::new().with(serialize));
+        writer.framed_write(Command::Say("yo".into())).unwrap();
+        writer.framed_write(Command::Ping).unwrap();
+        assert_eq!(
+            writer.into_inner(),
+            vec![0, 0, 0, 3, 1, b'y', b'o', 0, 0, 0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn map_both_ways() {
+        let codec = LengthDelimitedCodec::new().map(parse).with(serialize);
+        let mut wire = vec![];
+        let mut writer = FramedWrite::new(&mut wire, codec.clone());
+        writer.framed_write(Command::Say("back".into())).unwrap();
+        writer.framed_write(Command::Ping).unwrap();
+        drop(writer);
+
+        let mut echo = vec![];
+        let mut framed = Framed::with_codec(&wire[..], &mut echo, codec);
+        let cmd = framed.framed_read().unwrap();
+        assert_eq!(cmd, Command::Say("back".into()));
+        framed.framed_write(cmd).unwrap();
+        assert_eq!(framed.framed_read().unwrap(), Command::Ping);
+        drop(framed);
+        assert_eq!(&echo[..], &wire[..9]);
+    }
+}
@@ -0,0 +1,204 @@
+//! A codec for streams carrying several channels of frames.
+//!
+//! Each chunk on the wire belongs to one channel, given by a big-endian
+//! `u16` channel id, and is framed with a big-endian `u32` length header
+//! as [`LengthDelimitedCodec`] does:
+//!
+//! ```text
+//! +-----------------+---------------+---------------------+
+//! | channel id (u16)| length (u32)  |    chunk payload    |
+//! +-----------------+---------------+---------------------+
+//! ```
+//!
+//! The payloads of a channel's chunks are joined together in a buffer of
+//! its own and decoded by a decoder of its own, so a channel's frames may
+//! be split across chunks, or several of them sent in one chunk, without
+//! disturbing the other channels. Each channel's decoder is a clone of the
+//! one given to [`Multiplexed::new`], made when the channel is first seen.
+
+use alloc::collections::BTreeMap;
+use bytes::{Buf, BufMut, BytesMut};
+
+use crate::codec::LengthDelimitedCodec;
+use crate::io;
+use crate::{Decoder, Encoder};
+
+/// Identifies a channel of a [`Multiplexed`] stream.
+pub type ChannelId = u16;
+
+const CHANNEL_ID_LEN: usize = 2;
+
+#[derive(Debug, Clone)]
+struct Channel<D> {
+    codec: D,
+    buf: BytesMut,
+}
+
+/// Splits a stream into channels, decoding each with its own copy of `D`
+/// and yielding frames as `(channel, item)` pairs.
+///
+/// When one chunk completes several frames, the later ones are decoded from
+/// the channel's buffer without consuming anything from the stream, so this
+/// codec does not suit [`crate::FramedRead::set_check_progress`].
+#[derive(Debug, Clone)]
+pub struct Multiplexed<D> {
+    template: D,
+    channels: BTreeMap<ChannelId, Channel<D>>,
+    framing: LengthDelimitedCodec,
+    // The channel id of the chunk being received, once it has been read
+    pending: Option<ChannelId>,
+    // A channel fed by the last chunk that may hold more complete frames
+    ready: Option<ChannelId>,
+    scratch: BytesMut,
+}
+
+impl<D: Clone> Multiplexed<D> {
+    pub fn new(decoder: D) -> Self {
+        Self {
+            template: decoder,
+            channels: BTreeMap::new(),
+            framing: LengthDelimitedCodec::new(),
+            pending: None,
+            ready: None,
+            scratch: BytesMut::new(),
+        }
+    }
+
+    /// Returns the maximum length of a chunk's payload.
+    pub fn max_frame_length(&self) -> usize {
+        self.framing.max_frame_length()
+    }
+
+    /// Sets the maximum length of a chunk's payload.
+    pub fn set_max_frame_length(&mut self, max_frame_length: usize) {
+        self.framing.set_max_frame_length(max_frame_length);
+    }
+
+    /// Returns a reference to the codec of `channel`, if it has been seen.
+    pub fn channel(&self, channel: ChannelId) -> Option<&D> {
+        self.channels.get(&channel).map(|c| &c.codec)
+    }
+
+    /// Forget `channel`, dropping its codec and any bytes of a partial
+    /// frame. Should the channel be seen again it starts afresh.
+    pub fn remove_channel(&mut self, channel: ChannelId) -> Option<D> {
+        if self.ready == Some(channel) {
+            self.ready = None;
+        }
+        self.channels.remove(&channel).map(|c| c.codec)
+    }
+
+    fn channel_mut(&mut self, channel: ChannelId) -> &mut Channel<D> {
+        channel_entry(&mut self.channels, &self.template, channel)
+    }
+}
+
+fn channel_entry<'a, D: Clone>(
+    channels: &'a mut BTreeMap<ChannelId, Channel<D>>,
+    template: &D,
+    channel: ChannelId,
+) -> &'a mut Channel<D> {
+    channels.entry(channel).or_insert_with(|| Channel {
+        codec: template.clone(),
+        buf: BytesMut::new(),
+    })
+}
+
+impl<D: Decoder + Clone> Decoder for Multiplexed<D> {
+    type Item = (ChannelId, D::Item);
+    type Error = D::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            // Drain the channel fed last before reading another chunk
+            if let Some(id) = self.ready {
+                let channel = self.channel_mut(id);
+                if let Some(item) = channel.codec.decode(&mut channel.buf)? {
+                    return Ok(Some((id, item)));
+                }
+                self.ready = None;
+            }
+
+            if self.pending.is_none() {
+                if src.len() < CHANNEL_ID_LEN {
+                    return Ok(None);
+                }
+                let id = ChannelId::from_be_bytes([src[0], src[1]]);
+                src.advance(CHANNEL_ID_LEN);
+                self.pending = Some(id);
+            }
+            let Some(payload) = self.framing.decode(src)? else {
+                return Ok(None);
+            };
+            let id = self.pending.take().unwrap();
+            self.channel_mut(id).buf.extend_from_slice(&payload);
+            self.ready = Some(id);
+        }
+    }
+}
+
+impl<D, I> Encoder<(ChannelId, I)> for Multiplexed<D>
+where
+    D: Encoder<I> + Clone,
+    D::Error: From<io::Error>,
+{
+    type Error = D::Error;
+
+    fn encode(
+        &mut self,
+        (id, item): (ChannelId, I),
+        dst: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        let channel = channel_entry(&mut self.channels, &self.template, id);
+        self.scratch.clear();
+        channel.codec.encode(item, &mut self.scratch)?;
+        dst.reserve(CHANNEL_ID_LEN);
+        dst.put_u16(id);
+        self.framing.encode(self.scratch.split(), dst)?;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::codec::LinesCodec;
+    use crate::{FramedRead, FramedReader, FramedWrite, FramedWriter};
+
+    #[test]
+    fn interleaved_channels() {
+        let mut framed = FramedWrite::new(vec![], Multiplexed::new(LinesCodec::new()));
+        framed.framed_write((1, "one")).unwrap();
+        framed.framed_write((2, "two")).unwrap();
+        framed.framed_write((1, "three")).unwrap();
+        let mut wire = framed.into_inner();
+        assert_eq!(&wire[..10], b"\x00\x01\x00\x00\x00\x04one\n");
+
+        // A line split across two chunks, each interrupted by another
+        // channel, and a chunk holding two lines
+        for (id, chunk) in [(2, &b"fo"[..]), (1, b"a\nb\n"), (2, b"ur\n")] {
+            wire.extend_from_slice(&u16::to_be_bytes(id));
+            wire.extend_from_slice(&u32::to_be_bytes(chunk.len() as u32));
+            wire.extend_from_slice(chunk);
+        }
+
+        let mut framed =
+            FramedRead::with_capacity(&wire[..], Multiplexed::new(LinesCodec::new()), 3);
+        let mut frames = vec![];
+        while let Ok(frame) = framed.framed_read() {
+            frames.push(frame);
+        }
+        let frames: Vec<_> = frames.iter().map(|(id, s)| (*id, s.as_str())).collect();
+        assert_eq!(
+            frames,
+            [
+                (1, "one"),
+                (2, "two"),
+                (1, "three"),
+                (1, "a"),
+                (1, "b"),
+                (2, "four")
+            ]
+        );
+    }
+}
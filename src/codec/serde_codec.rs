@@ -0,0 +1,409 @@
+//! Codecs for sending types that implement `serde`'s `Serialize` and
+//! `Deserialize` traits.
+//!
+//! [`JsonCodec`] is available with the `json` feature, [`BincodeCodec`]
+//! with the `bincode` feature and [`MsgpackCodec`] with the `rmp-serde`
+//! feature. Each frames the serialized payload with a
+//! big-endian `u32` length header, as [`LengthDelimitedCodec`] does, so a
+//! decoder waits until the whole payload has arrived before deserializing
+//! it. A payload that cannot be deserialized is reported as an error of
+//! kind `ErrorKind::InvalidData`.
+
+use bytes::{BufMut, BytesMut};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::io::{self, Error, ErrorKind};
+use std::marker::PhantomData;
+
+use crate::codec::LengthDelimitedCodec;
+use crate::{Decoder, Encoder};
+
+/// Sends values of type `T` as length prefixed JSON.
+///
+/// JSON is easy to inspect and to produce from other languages, at the
+/// cost of larger frames than [`BincodeCodec`].
+#[cfg(feature = "json")]
+pub struct JsonCodec<T> {
+    framing: LengthDelimitedCodec,
+    _item: PhantomData<fn() -> T>,
+}
+
+#[cfg(feature = "json")]
+impl<T> JsonCodec<T> {
+    /// Create a codec with an 8 MiB maximum payload length.
+    pub fn new() -> Self {
+        Self {
+            framing: LengthDelimitedCodec::new(),
+            _item: PhantomData,
+        }
+    }
+
+    /// Returns the maximum length of a serialized payload.
+    pub fn max_frame_length(&self) -> usize {
+        self.framing.max_frame_length()
+    }
+
+    /// Sets the maximum length of a serialized payload.
+    pub fn set_max_frame_length(&mut self, max_frame_length: usize) {
+        self.framing.set_max_frame_length(max_frame_length);
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T> Default for JsonCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T> Clone for JsonCodec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            framing: self.framing.clone(),
+            _item: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T> fmt::Debug for JsonCodec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonCodec")
+            .field("max_frame_length", &self.max_frame_length())
+            .finish()
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T: DeserializeOwned> Decoder for JsonCodec<T> {
+    type Item = T;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.framing.decode(src)? {
+            Some(payload) => serde_json::from_slice(&payload)
+                .map(Some)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e)),
+            None => Ok(None),
+        }
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        self.framing.expected_len(src)
+    }
+}
+
+#[cfg(feature = "json")]
+fn json_payload<T: Serialize>(item: &T) -> io::Result<BytesMut> {
+    let mut payload = BytesMut::new().writer();
+    serde_json::to_writer(&mut payload, item)
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    Ok(payload.into_inner())
+}
+
+#[cfg(feature = "json")]
+impl<T: Serialize> Encoder<T> for JsonCodec<T> {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.framing.encode(json_payload(&item)?, dst)
+    }
+
+    fn encode_ref(&mut self, item: &T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.framing.encode(json_payload(item)?, dst)
+    }
+}
+
+/// Sends values of type `T` as length prefixed bincode.
+///
+/// Bincode is a compact binary format, best suited to talking to other
+/// programs that also use `serde` and bincode.
+#[cfg(feature = "bincode")]
+pub struct BincodeCodec<T> {
+    framing: LengthDelimitedCodec,
+    _item: PhantomData<fn() -> T>,
+}
+
+#[cfg(feature = "bincode")]
+impl<T> BincodeCodec<T> {
+    /// Create a codec with an 8 MiB maximum payload length.
+    pub fn new() -> Self {
+        Self {
+            framing: LengthDelimitedCodec::new(),
+            _item: PhantomData,
+        }
+    }
+
+    /// Returns the maximum length of a serialized payload.
+    pub fn max_frame_length(&self) -> usize {
+        self.framing.max_frame_length()
+    }
+
+    /// Sets the maximum length of a serialized payload.
+    pub fn set_max_frame_length(&mut self, max_frame_length: usize) {
+        self.framing.set_max_frame_length(max_frame_length);
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl<T> Default for BincodeCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl<T> Clone for BincodeCodec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            framing: self.framing.clone(),
+            _item: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl<T> fmt::Debug for BincodeCodec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BincodeCodec")
+            .field("max_frame_length", &self.max_frame_length())
+            .finish()
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl<T: DeserializeOwned> Decoder for BincodeCodec<T> {
+    type Item = T;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.framing.decode(src)? {
+            Some(payload) => bincode::deserialize(&payload)
+                .map(Some)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e)),
+            None => Ok(None),
+        }
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        self.framing.expected_len(src)
+    }
+}
+
+#[cfg(feature = "bincode")]
+fn bincode_payload<T: Serialize>(item: &T) -> io::Result<BytesMut> {
+    let mut payload = BytesMut::new().writer();
+    bincode::serialize_into(&mut payload, item)
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    Ok(payload.into_inner())
+}
+
+#[cfg(feature = "bincode")]
+impl<T: Serialize> Encoder<T> for BincodeCodec<T> {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.framing.encode(bincode_payload(&item)?, dst)
+    }
+
+    fn encode_ref(&mut self, item: &T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.framing.encode(bincode_payload(item)?, dst)
+    }
+}
+
+/// Sends values of type `T` as length prefixed MessagePack.
+///
+/// MessagePack is a compact binary format with libraries for most
+/// languages. Structs are written as maps keyed by field name so that
+/// programs in other languages can read them.
+#[cfg(feature = "rmp-serde")]
+pub struct MsgpackCodec<T> {
+    framing: LengthDelimitedCodec,
+    _item: PhantomData<fn() -> T>,
+}
+
+#[cfg(feature = "rmp-serde")]
+impl<T> MsgpackCodec<T> {
+    /// Create a codec with an 8 MiB maximum payload length.
+    pub fn new() -> Self {
+        Self {
+            framing: LengthDelimitedCodec::new(),
+            _item: PhantomData,
+        }
+    }
+
+    /// Returns the maximum length of a serialized payload.
+    pub fn max_frame_length(&self) -> usize {
+        self.framing.max_frame_length()
+    }
+
+    /// Sets the maximum length of a serialized payload.
+    pub fn set_max_frame_length(&mut self, max_frame_length: usize) {
+        self.framing.set_max_frame_length(max_frame_length);
+    }
+}
+
+#[cfg(feature = "rmp-serde")]
+impl<T> Default for MsgpackCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "rmp-serde")]
+impl<T> Clone for MsgpackCodec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            framing: self.framing.clone(),
+            _item: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "rmp-serde")]
+impl<T> fmt::Debug for MsgpackCodec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MsgpackCodec")
+            .field("max_frame_length", &self.max_frame_length())
+            .finish()
+    }
+}
+
+#[cfg(feature = "rmp-serde")]
+impl<T: DeserializeOwned> Decoder for MsgpackCodec<T> {
+    type Item = T;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.framing.decode(src)? {
+            Some(payload) => rmp_serde::from_slice(&payload)
+                .map(Some)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e)),
+            None => Ok(None),
+        }
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        self.framing.expected_len(src)
+    }
+}
+
+#[cfg(feature = "rmp-serde")]
+fn msgpack_payload<T: Serialize>(item: &T) -> io::Result<BytesMut> {
+    let mut payload = BytesMut::new().writer();
+    rmp_serde::encode::write_named(&mut payload, item)
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    Ok(payload.into_inner())
+}
+
+#[cfg(feature = "rmp-serde")]
+impl<T: Serialize> Encoder<T> for MsgpackCodec<T> {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.framing.encode(msgpack_payload(&item)?, dst)
+    }
+
+    fn encode_ref(&mut self, item: &T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.framing.encode(msgpack_payload(item)?, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FramedRead, FramedReader, FramedWrite, FramedWriter};
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Reading {
+        sensor: String,
+        values: Vec<i32>,
+        ok: bool,
+    }
+
+    fn readings() -> Vec<Reading> {
+        vec![
+            Reading {
+                sensor: "north".to_string(),
+                values: vec![1, -2, 3],
+                ok: true,
+            },
+            Reading {
+                sensor: "south".to_string(),
+                values: vec![],
+                ok: false,
+            },
+        ]
+    }
+
+    fn round_trip<C>(codec: C)
+    where
+        C: Decoder<Item = Reading, Error = io::Error> + Encoder<Reading, Error = io::Error>,
+        C: Clone,
+    {
+        let mut framed = FramedWrite::new(Vec::new(), codec.clone());
+        framed.framed_write_all(readings()).unwrap();
+        let wire = framed.into_inner();
+
+        // A small capacity delivers each frame across several reads
+        let mut framed = FramedRead::with_capacity(&wire[..], codec, 5);
+        for expected in readings() {
+            assert_eq!(framed.framed_read().unwrap(), expected);
+        }
+        assert!(framed.framed_read().is_err());
+    }
+
+    fn malformed<C: Decoder<Error = io::Error>>(mut codec: C) {
+        let mut src = BytesMut::from(&[0, 0, 0, 3, 0xff, 0xfe, 0xfd][..]);
+        let err = codec.decode(&mut src).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_round_trip() {
+        round_trip(JsonCodec::new());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_partial_and_malformed() {
+        let mut codec = JsonCodec::<Reading>::new();
+        let mut src = BytesMut::from(&b"\x00\x00\x00\x0e{\"sensor\":"[..]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        malformed(codec);
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_round_trip() {
+        round_trip(BincodeCodec::new());
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_partial_and_malformed() {
+        let mut codec = BincodeCodec::<Reading>::new();
+        let mut src = BytesMut::from(&[0, 0, 0, 20, 5, 0][..]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        malformed(codec);
+    }
+
+    #[cfg(feature = "rmp-serde")]
+    #[test]
+    fn msgpack_round_trip() {
+        round_trip(MsgpackCodec::new());
+    }
+
+    #[cfg(feature = "rmp-serde")]
+    #[test]
+    fn msgpack_partial_and_malformed() {
+        let mut codec = MsgpackCodec::<Reading>::new();
+        let mut src = BytesMut::from(&[0, 0, 0, 30, 0x83, 0xa6][..]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        malformed(codec);
+    }
+}
@@ -0,0 +1,178 @@
+//! A codec for the Serial Line Internet Protocol (SLIP) framing of RFC 1055.
+//!
+//! SLIP ends each frame with an `END` byte, `0xC0`. An `END` or `ESC`
+//! (`0xDB`) byte in the payload is sent as a two byte escape sequence:
+//!
+//! | payload byte | on the wire            |
+//! |--------------|------------------------|
+//! | `END` 0xC0   | `ESC` 0xDB, `ESC_END` 0xDC |
+//! | `ESC` 0xDB   | `ESC` 0xDB, `ESC_ESC` 0xDD |
+//!
+//! Like COBS it is self synchronising: after line noise or a corrupt frame
+//! the receiver carries on from the next `END` byte.
+
+use bytes::{BufMut, BytesMut};
+
+use crate::io::{self, Error, ErrorKind};
+use crate::{Decoder, Encoder};
+
+const END: u8 = 0xc0;
+const ESC: u8 = 0xdb;
+const ESC_END: u8 = 0xdc;
+const ESC_ESC: u8 = 0xdd;
+
+/// Splits a stream into SLIP frames terminated by an `END` byte.
+///
+/// Decoded items are the original payload bytes. A frame holding an `ESC`
+/// byte that is not followed by `ESC_END` or `ESC_ESC` is consumed, up to
+/// and including its `END`, and reported as an error of kind
+/// `ErrorKind::InvalidData`, so calling `decode` again carries on with the
+/// next frame. Empty frames are skipped. When encoding, an `END` byte is
+/// sent before each frame as well as after it, as RFC 1055 suggests, so
+/// that any noise on the line ends up in an empty or corrupt frame of its
+/// own.
+///
+/// Note that [`crate::FramedRead`] stops reading after any decoder error,
+/// so to carry on past corrupt frames decode from the buffer directly.
+#[derive(Debug, Clone, Default)]
+pub struct SlipCodec {
+    // Where to resume searching for an `END` on the next call to `decode`
+    next_index: usize,
+}
+
+impl SlipCodec {
+    pub fn new() -> Self {
+        Self { next_index: 0 }
+    }
+}
+
+fn slip_decode(src: &[u8]) -> io::Result<BytesMut> {
+    let mut dst = BytesMut::with_capacity(src.len());
+    let mut bytes = src.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte != ESC {
+            dst.put_u8(byte);
+            continue;
+        }
+        match bytes.next() {
+            Some(&ESC_END) => dst.put_u8(END),
+            Some(&ESC_ESC) => dst.put_u8(ESC),
+            _ => return Err(Error::new(ErrorKind::InvalidData, "invalid SLIP escape")),
+        }
+    }
+    Ok(dst)
+}
+
+impl Decoder for SlipCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            let start = self.next_index.min(src.len());
+            let end = match src[start..].iter().position(|b| *b == END) {
+                Some(offset) => start + offset,
+                None => {
+                    self.next_index = src.len();
+                    return Ok(None);
+                }
+            };
+            self.next_index = 0;
+            let frame = src.split_to(end + 1);
+            if end > 0 {
+                return slip_decode(&frame[..end]).map(Some);
+            }
+        }
+    }
+}
+
+fn escaped_len(item: &[u8]) -> usize {
+    item.len() + item.iter().filter(|b| matches!(**b, END | ESC)).count()
+}
+
+impl Encoder<BytesMut> for SlipCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: BytesMut, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_ref(&item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &BytesMut, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.reserve(escaped_len(item) + 2);
+        dst.put_u8(END);
+        for &byte in item {
+            match byte {
+                END => dst.put_slice(&[ESC, ESC_END]),
+                ESC => dst.put_slice(&[ESC, ESC_ESC]),
+                byte => dst.put_u8(byte),
+            }
+        }
+        dst.put_u8(END);
+        Ok(())
+    }
+
+    fn size_hint(&self, item: &BytesMut) -> Option<usize> {
+        Some(escaped_len(item) + 2)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{FramedRead, FramedReader};
+
+    fn encode(payload: &[u8]) -> BytesMut {
+        let mut dst = BytesMut::new();
+        let item = BytesMut::from(payload);
+        let mut codec = SlipCodec::new();
+        assert_eq!(codec.size_hint(&item), Some(escaped_len(payload) + 2));
+        codec.encode(item, &mut dst).unwrap();
+        dst
+    }
+
+    #[test]
+    fn normal_frames() {
+        assert_eq!(&encode(b"\x11\x22")[..], b"\xc0\x11\x22\xc0");
+
+        let mut r = encode(b"\x11\x22").to_vec();
+        r.extend_from_slice(&encode(b"\x33"));
+        let mut framed = FramedRead::with_capacity(&r[..], SlipCodec::new(), 3);
+        assert_eq!(&framed.framed_read().unwrap()[..], &[0x11, 0x22]);
+        assert_eq!(&framed.framed_read().unwrap()[..], &[0x33]);
+        assert!(framed.framed_read().is_err());
+    }
+
+    #[test]
+    fn special_bytes_in_payload() {
+        let payload = [END, 1, ESC, ESC_END, END, ESC];
+        let wire = encode(&payload);
+        assert_eq!(
+            &wire[..],
+            &[END, ESC, ESC_END, 1, ESC, ESC_ESC, ESC_END, ESC, ESC_END, ESC, ESC_ESC, END]
+        );
+        assert!(!wire[1..wire.len() - 1].contains(&END));
+
+        let mut src = wire;
+        let decoded = SlipCodec::new().decode(&mut src).unwrap().unwrap();
+        assert_eq!(&decoded[..], &payload);
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn recover_after_corrupt_escape() {
+        let mut codec = SlipCodec::new();
+        let mut src = BytesMut::from(&[END, 0xaa, ESC, 0x01][..]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        src.extend_from_slice(&[0xbb, END]);
+        // An escape cut short by the end of the frame
+        src.extend_from_slice(&[ESC, END]);
+        src.extend_from_slice(&encode(b"ok"));
+
+        for _ in 0..2 {
+            let err = codec.decode(&mut src).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+        assert_eq!(&codec.decode(&mut src).unwrap().unwrap()[..], b"ok");
+        assert!(src.is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+//! An adapter for using framous codecs with `tokio_util`.
+//!
+//! Only available with the `tokio` feature.
+//!
+//! The `tokio_util` codec traits have almost the same shape as
+//! [`Decoder`] and [`Encoder`], so a codec written for synchronous use can be
+//! wrapped in an [`AsyncCodec`] and handed to `tokio_util::codec::FramedRead`,
+//! `FramedWrite` or `Framed` unchanged:
+//!
+//! ```
+//! use framous::codec::{into_async, LinesCodec};
+//!
+//! # async fn run(stream: tokio::io::DuplexStream) {
+//! let framed = tokio_util::codec::Framed::new(stream, into_async(LinesCodec::new()));
+//! # }
+//! ```
+
+use bytes::BytesMut;
+
+use crate::{Decoder, Encoder};
+
+/// Wraps a framous codec so that it implements the `tokio_util` codec traits.
+///
+/// Decoding and encoding are forwarded to the inner codec, including
+/// [`Decoder::decode_eof`], so a codec behaves the same way in both worlds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsyncCodec<C> {
+    inner: C,
+}
+
+impl<C> AsyncCodec<C> {
+    pub fn new(inner: C) -> Self {
+        Self { inner }
+    }
+
+    /// Returns a reference to the inner codec.
+    pub fn get_ref(&self) -> &C {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner codec.
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+
+    /// Consumes the `AsyncCodec`, returning the inner codec.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+/// Wraps `codec` in an [`AsyncCodec`] for use with `tokio_util`.
+pub fn into_async<C>(codec: C) -> AsyncCodec<C> {
+    AsyncCodec::new(codec)
+}
+
+impl<C: Decoder> tokio_util::codec::Decoder for AsyncCodec<C> {
+    type Item = C::Item;
+    type Error = C::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.inner.decode(src)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.inner.decode_eof(src)
+    }
+}
+
+impl<I, C: Encoder<I>> tokio_util::codec::Encoder<I> for AsyncCodec<C> {
+    type Error = C::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if let Some(hint) = self.inner.size_hint(&item) {
+            dst.reserve(hint);
+        }
+        self.inner.encode(item, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{LengthDelimitedCodec, LinesCodec};
+    use futures::{SinkExt, StreamExt};
+    use std::io;
+
+    fn assert_tokio_codec<C>()
+    where
+        C: tokio_util::codec::Decoder + tokio_util::codec::Encoder<BytesMut>,
+    {
+    }
+
+    #[test]
+    fn implements_tokio_traits() {
+        assert_tokio_codec::<AsyncCodec<LengthDelimitedCodec>>();
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        // A small duplex buffer makes the frames arrive in pieces
+        let (client, server) = tokio::io::duplex(4);
+
+        let writer = tokio::spawn(async move {
+            let mut framed =
+                tokio_util::codec::FramedWrite::new(client, into_async(LinesCodec::new()));
+            framed.send("hello").await?;
+            framed.send("and goodbye").await?;
+            Ok::<_, io::Error>(())
+        });
+
+        let mut framed = tokio_util::codec::FramedRead::new(server, into_async(LinesCodec::new()));
+        assert_eq!(framed.next().await.unwrap().unwrap(), "hello");
+        assert_eq!(framed.next().await.unwrap().unwrap(), "and goodbye");
+        assert!(framed.next().await.is_none());
+        writer.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+//! Framing for connectionless sockets such as `UdpSocket`.
+//!
+//! A [`crate::FramedRead`] decodes a stream of bytes, which has no notion
+//! of where each byte came from. A [`FramedDatagram`] instead receives one
+//! datagram at a time and decodes it as exactly one frame, returning the
+//! frame along with the address of its sender, and sends each encoded frame
+//! as a datagram of its own.
+//!
+//! A datagram arrives whole or not at all, so there is no partial frame to
+//! wait for and each one is decoded with [`Decoder::decode_eof`]. A
+//! datagram that the decoder cannot turn into exactly one frame, because
+//! it holds too few bytes or bytes left over, is an error of kind
+//! `ErrorKind::InvalidData`. That datagram is dropped and the next receive
+//! carries on as normal, so unlike a `FramedRead` a `FramedDatagram` is not
+//! left unusable by a bad frame.
+
+use std::fmt;
+use std::io::{self, Error, ErrorKind};
+use std::net::{SocketAddr, UdpSocket};
+
+use bytes::BytesMut;
+
+use crate::codec::decode_whole;
+use crate::{Decoder, Encoder};
+
+// The largest payload a UDP datagram can carry
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// A socket that sends and receives whole datagrams, such as a `UdpSocket`.
+pub trait DatagramSocket {
+    /// Receive a single datagram into `buf`, returning its length and the
+    /// address it came from. See `UdpSocket::recv_from`.
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)>;
+
+    /// Send `buf` as a single datagram to `addr`. See `UdpSocket::send_to`.
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize>;
+}
+
+impl DatagramSocket for UdpSocket {
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        UdpSocket::recv_from(self, buf)
+    }
+
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        UdpSocket::send_to(self, buf, addr)
+    }
+}
+
+impl<T: DatagramSocket + ?Sized> DatagramSocket for &T {
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        (**self).recv_from(buf)
+    }
+
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        (**self).send_to(buf, addr)
+    }
+}
+
+/// Sends and receives frames over a [`DatagramSocket`], one frame per
+/// datagram.
+///
+/// Iterating over a `FramedDatagram` receives frames one after another,
+/// each with the address of its sender, and never returns `None`.
+pub struct FramedDatagram<S, C> {
+    socket: S,
+    codec: C,
+    // Receives each datagram, sized to the largest one allowed
+    recv_buf: Vec<u8>,
+    rd: BytesMut,
+    wr: BytesMut,
+}
+
+impl<S, C> FramedDatagram<S, C> {
+    pub fn new(socket: S, codec: C) -> Self {
+        Self {
+            socket,
+            codec,
+            recv_buf: vec![0; MAX_DATAGRAM_SIZE],
+            rd: BytesMut::new(),
+            wr: BytesMut::new(),
+        }
+    }
+
+    /// Sets the size of the largest datagram that can be received.
+    /// The default is 65,507 bytes, the most a UDP datagram can carry.
+    ///
+    /// Bytes of a larger datagram that do not fit are discarded by the
+    /// socket, which usually leaves a frame the decoder cannot use.
+    pub fn set_max_datagram_size(&mut self, size: usize) {
+        self.recv_buf.resize(size, 0);
+    }
+
+    /// Returns a reference to the underlying socket.
+    pub fn get_ref(&self) -> &S {
+        &self.socket
+    }
+
+    /// Returns a mutable reference to the underlying socket.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.socket
+    }
+
+    /// Returns a reference to the codec.
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// Returns a mutable reference to the codec.
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    /// Consumes the `FramedDatagram`, returning the underlying socket.
+    pub fn into_inner(self) -> S {
+        self.socket
+    }
+}
+
+impl<S: DatagramSocket, C: Decoder> FramedDatagram<S, C> {
+    /// Receive a datagram and decode it as a single frame, returning the
+    /// frame and the address of its sender.
+    pub fn recv_from(&mut self) -> Result<(C::Item, SocketAddr), C::Error> {
+        let (len, addr) = loop {
+            match self.socket.recv_from(&mut self.recv_buf) {
+                Ok(received) => break received,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        };
+        self.rd.clear();
+        self.rd.extend_from_slice(&self.recv_buf[..len]);
+        let item = decode_whole(&mut self.codec, &mut self.rd)?;
+        Ok((item, addr))
+    }
+}
+
+impl<S: DatagramSocket, C> FramedDatagram<S, C> {
+    /// Encode `item` and send it to `addr` as a single datagram.
+    pub fn send_to<I>(&mut self, item: I, addr: SocketAddr) -> Result<(), C::Error>
+    where
+        C: Encoder<I>,
+    {
+        self.wr.clear();
+        self.codec.encode(item, &mut self.wr)?;
+        let sent = self.socket.send_to(&self.wr, addr)?;
+        if sent != self.wr.len() {
+            return Err(Error::new(ErrorKind::WriteZero, "failed to send whole frame").into());
+        }
+        Ok(())
+    }
+}
+
+impl<S: fmt::Debug, C: fmt::Debug> fmt::Debug for FramedDatagram<S, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FramedDatagram")
+            .field("socket", &self.socket)
+            .field("codec", &self.codec)
+            .field("max_datagram_size", &self.recv_buf.len())
+            .finish()
+    }
+}
+
+impl<S: DatagramSocket, C: Decoder> Iterator for FramedDatagram<S, C> {
+    type Item = Result<(C::Item, SocketAddr), C::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.recv_from())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{LengthDelimitedCodec, LinesCodec};
+
+    #[test]
+    fn loopback_round_trip() {
+        let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let a_addr = a.local_addr().unwrap();
+        let b_addr = b.local_addr().unwrap();
+
+        let mut a = FramedDatagram::new(a, LinesCodec::new());
+        let mut b = FramedDatagram::new(b, LinesCodec::new());
+        a.send_to("hello", b_addr).unwrap();
+        assert_eq!(b.recv_from().unwrap(), ("hello".to_string(), a_addr));
+        b.send_to("world", a_addr).unwrap();
+        assert_eq!(a.next().unwrap().unwrap(), ("world".to_string(), b_addr));
+    }
+
+    #[test]
+    fn datagram_must_hold_one_frame() {
+        let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let b_addr = b.local_addr().unwrap();
+        let mut b = FramedDatagram::new(b, LengthDelimitedCodec::new());
+
+        // A truncated frame and one followed by a second
+        for bad in [&[0u8, 0, 0, 5, 1][..], &[0, 0, 0, 1, 1, 0, 0, 0, 1, 2]] {
+            a.send_to(bad, b_addr).unwrap();
+            let err = b.recv_from().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+
+        // Later datagrams are unaffected
+        a.send_to(&[0, 0, 0, 1, 7], b_addr).unwrap();
+        assert_eq!(&b.recv_from().unwrap().0[..], &[7]);
+    }
+}
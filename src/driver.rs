@@ -0,0 +1,177 @@
+//! A minimal framing layer that works without `std`.
+//!
+//! [`FrameReader`] and [`FrameWriter`] drive a [`Decoder`] or [`Encoder`]
+//! over the [`Read`] and [`Write`] traits from [`crate::io`], which are the
+//! `std::io` ones when the `std` feature is enabled and a small stand-in
+//! otherwise. On an embedded target, implement those two traits for the
+//! serial port or other byte interface and use the same codecs as on a
+//! desktop.
+//!
+//! These types do far less than [`crate::FramedRead`] and
+//! [`crate::FramedWrite`]: no timeouts, buffer limits or heartbeats. Prefer
+//! those whenever `std` is available.
+
+use bytes::BytesMut;
+
+use crate::io::{Error, ErrorKind, Read, Write};
+use crate::{Decoder, Encoder};
+
+const DEFAULT_CHUNK_SIZE: usize = 256;
+
+/// Reads frames from any [`Read`] object.
+#[derive(Debug)]
+pub struct FrameReader<R, D> {
+    inner: R,
+    decoder: D,
+    buf: BytesMut,
+    chunk_size: usize,
+}
+
+impl<R, D> FrameReader<R, D> {
+    /// Create a `FrameReader` that asks for up to 256 bytes on each read.
+    pub fn new(inner: R, decoder: D) -> Self {
+        Self::with_chunk_size(inner, decoder, DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Create a `FrameReader` that asks for up to `chunk_size` bytes on
+    /// each read.
+    ///
+    /// # Panics
+    /// If `chunk_size` is zero.
+    pub fn with_chunk_size(inner: R, decoder: D, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "chunk size must be greater than zero");
+        Self {
+            inner,
+            decoder,
+            buf: BytesMut::new(),
+            chunk_size,
+        }
+    }
+
+    /// Returns a reference to the bytes that have been read but not yet decoded.
+    pub fn read_buffer(&self) -> &BytesMut {
+        &self.buf
+    }
+
+    /// Consumes the `FrameReader`, returning the underlying `Read` object.
+    /// Any bytes that have been read but not yet decoded are lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read, D: Decoder> FrameReader<R, D> {
+    /// Read the next frame.
+    ///
+    /// Frames already in the buffer are decoded before reading any more.
+    /// Returns `Ok(None)` once the stream ends with no bytes left over,
+    /// while the stream ending part way through a frame is an error of
+    /// kind `ErrorKind::UnexpectedEof`.
+    pub fn read_frame(&mut self) -> Result<Option<D::Item>, D::Error> {
+        loop {
+            if let Some(item) = self.decoder.decode(&mut self.buf)? {
+                return Ok(Some(item));
+            }
+
+            let len = self.buf.len();
+            self.buf.resize(len + self.chunk_size, 0);
+            let bytes_read = loop {
+                match self.inner.read(&mut self.buf[len..]) {
+                    Ok(n) => break n,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        self.buf.truncate(len);
+                        return Err(e.into());
+                    }
+                }
+            };
+            self.buf.truncate(len + bytes_read);
+
+            if bytes_read == 0 {
+                if let Some(item) = self.decoder.decode_eof(&mut self.buf)? {
+                    return Ok(Some(item));
+                }
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "stream ended part way through a frame",
+                )
+                .into());
+            }
+        }
+    }
+}
+
+/// Writes frames to any [`Write`] object.
+#[derive(Debug)]
+pub struct FrameWriter<W, E> {
+    inner: W,
+    encoder: E,
+    buf: BytesMut,
+}
+
+impl<W, E> FrameWriter<W, E> {
+    pub fn new(inner: W, encoder: E) -> Self {
+        Self {
+            inner,
+            encoder,
+            buf: BytesMut::new(),
+        }
+    }
+
+    /// Consumes the `FrameWriter`, returning the underlying `Write` object.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write, E> FrameWriter<W, E> {
+    /// Encode `item`, write it out and flush the underlying `Write` object.
+    pub fn write_frame<I>(&mut self, item: I) -> Result<(), E::Error>
+    where
+        E: Encoder<I>,
+    {
+        self.buf.clear();
+        if let Some(hint) = self.encoder.size_hint(&item) {
+            self.buf.reserve(hint);
+        }
+        self.encoder.encode(item, &mut self.buf)?;
+        self.inner.write_all(&self.buf)?;
+        self.inner.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{CobsCodec, LengthDelimitedCodec, LinesCodec};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn round_trip() {
+        let mut writer = FrameWriter::new(Vec::new(), CobsCodec::new());
+        writer.write_frame(BytesMut::from(&b"a\0b"[..])).unwrap();
+        writer.write_frame(BytesMut::from(&b"cd"[..])).unwrap();
+        let wire = writer.into_inner();
+
+        let mut reader = FrameReader::with_chunk_size(&wire[..], CobsCodec::new(), 3);
+        assert_eq!(&reader.read_frame().unwrap().unwrap()[..], b"a\0b");
+        assert_eq!(&reader.read_frame().unwrap().unwrap()[..], b"cd");
+        assert!(reader.read_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn eof_mid_frame() {
+        let mut reader = FrameReader::new(&b"one\ntwo"[..], LinesCodec::new());
+        assert_eq!(reader.read_frame().unwrap().unwrap(), "one");
+        assert_eq!(reader.read_frame().unwrap().unwrap(), "two");
+        assert!(reader.read_frame().unwrap().is_none());
+
+        let mut reader = FrameReader::new(&[0, 0, 0, 5, 1, 2][..], LengthDelimitedCodec::new());
+        let err = reader.read_frame().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+}
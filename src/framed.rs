@@ -9,299 +9,4992 @@
 //! object. This means that they will work in multi-threaded applications
 //! but this presents a problem in the very likely case when the `Read`
 //! and `Write` object is the same `TcpStream`. In this case use the
-//! `try_clone` method on the stream to get a thread-safe handle.
+//! `try_clone` method on the stream to get a thread-safe handle. In a
+//! single-threaded application `Framed::from_stream` avoids the clone by
+//! sharing one stream and one codec between the two halves.
 //!
 //! A `Framed` type is useful in single-threaded applications where you
 //! just want a single object to encode and decode. For multi-threaded
 //! applications you can use `FramedRead` in one thread and `FramedWrite`
 //! in another.
 //!
+//! The framed types can also borrow a stream instead of owning it, as
+//! `&mut TcpStream` (and `&TcpStream`) implement `Read` and `Write` too.
+//! A `FramedRead<&mut TcpStream, D>` lives no longer than the borrow, and
+//! once it is dropped the stream can be used directly again, for example
+//! to read an unframed trailer. Any bytes read past the last decoded frame
+//! are held in the read buffer, so take them with `read_buffer` before
+//! dropping the `FramedRead` if the protocol needs them.
+//!
 
-use std::io::{self, Error, ErrorKind, Read, Write};
+use std::fmt;
+use std::io::{
+    self, BufReader, Error, ErrorKind, IoSlice, Read, Seek, SeekFrom, StdinLock, StdoutLock, Write,
+};
+use std::mem::{self, ManuallyDrop};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(windows)]
+use std::os::windows::io::{AsRawSocket, RawSocket};
+use std::time::{Duration, Instant};
+use std::{ptr, slice};
 
-use bytes::BytesMut;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 
-use crate::{Decoder, Encoder};
+use crate::codec::{DecoderExt, EncoderExt, InspectDecoder, InspectEncoder};
+use crate::{Decoder, Encoder, Shared, Tee};
 
 const INITIAL_CAPACITY: usize = 8 * 1024;
+// By default the read buffer is shrunk once its capacity grows past this
+// multiple of the initial capacity
+const SHRINK_FACTOR: usize = 8;
+// By default a read returning no bytes is taken as EOF straight away, as
+// `Read` specifies, rather than retried
+const MAX_EMPTY_READS: usize = 0;
 
 pub struct FramedRead<R, D> {
     inner: R,
     decoder: D,
     buf: BytesMut,
+    capacity: usize,
+    read_chunk_size: usize,
+    shrink_threshold: Option<usize>,
+    max_buffer_size: Option<usize>,
+    max_empty_reads: usize,
+    // Set when the last read hit EOF with nothing left in the buffer
+    eof: bool,
+    // Set when the decoder has returned an error
+    poisoned: bool,
+    check_progress: bool,
+    // The buffer length when the decoder last asked for more bytes without
+    // consuming any
+    stalled_len: Option<usize>,
+    // Bytes read from the inner object since creation
+    total_read: u64,
+    // Set while bytes handed over at creation may still hold whole frames
+    seeded: bool,
 }
 
-impl<R, D> FramedRead<R, D> {
+impl<R, D: Decoder> FramedRead<R, D> {
+    /// Create a `FramedRead` whose read buffer starts with the capacity
+    /// given by [`Decoder::capacity_hint`], or 8 KiB if the decoder has no
+    /// hint.
     pub fn new(inner: R, decoder: D) -> Self {
+        let capacity = decoder.capacity_hint().unwrap_or(INITIAL_CAPACITY);
+        Self::with_capacity(inner, decoder, capacity)
+    }
+}
+
+impl<R, D> FramedRead<R, D> {
+    /// Create a `FramedRead` whose read buffer starts with `capacity` bytes.
+    /// This is also the number of bytes requested from the underlying
+    /// `Read` object on each read unless changed with
+    /// [`FramedRead::set_read_chunk_size`].
+    pub fn with_capacity(inner: R, decoder: D, capacity: usize) -> Self {
         Self {
             inner,
             decoder,
-            buf: BytesMut::with_capacity(INITIAL_CAPACITY),
+            buf: BytesMut::with_capacity(capacity),
+            capacity,
+            read_chunk_size: capacity,
+            shrink_threshold: Some(capacity.max(1) * SHRINK_FACTOR),
+            max_buffer_size: None,
+            max_empty_reads: MAX_EMPTY_READS,
+            eof: false,
+            poisoned: false,
+            check_progress: false,
+            stalled_len: None,
+            total_read: 0,
+            seeded: false,
         }
     }
-}
 
-pub struct FramedWrite<W, E> {
-    inner: W,
-    encoder: E,
-}
+    /// Create a `FramedRead` whose read buffer starts out holding `initial`,
+    /// bytes that were read from `inner` before framing began.
+    ///
+    /// This is the inverse of [`FramedRead::into_parts`]: after an unframed
+    /// handshake, hand over whatever was read past its end so that no bytes
+    /// are lost. Any whole frames in `initial` are decoded before anything
+    /// more is read from `inner`, so a frame that arrived along with the
+    /// handshake is returned straight away.
+    pub fn with_initial_buffer(inner: R, decoder: D, initial: BytesMut) -> Self {
+        let mut framed = Self::with_capacity(inner, decoder, INITIAL_CAPACITY);
+        framed.seeded = !initial.is_empty();
+        framed.buf = initial;
+        framed
+    }
 
-impl<W, E> FramedWrite<W, E> {
-    pub fn new(inner: W, encoder: E) -> Self {
-        Self { inner, encoder }
+    /// Create a `FramedRead` from a `BufReader`, taking over its buffered
+    /// bytes and then reading straight from the stream inside it.
+    ///
+    /// A `FramedRead` already buffers what it reads, so reading through a
+    /// `BufReader` as well copies every byte twice. The decoder needs the
+    /// bytes in a `BytesMut`, so rather than decoding from the `BufReader`'s
+    /// buffer this unwraps it: whatever it holds becomes the start of the
+    /// read buffer, as for [`FramedRead::with_initial_buffer`], and each
+    /// read asks the inner stream for as many bytes as the `BufReader`'s
+    /// capacity. This suits a `BufReader` that was used for an unframed
+    /// handshake, such as reading a greeting line with `read_line`. Frames
+    /// that arrived along with the greeting are decoded from those bytes
+    /// without waiting on the stream.
+    pub fn from_buf_read(reader: BufReader<R>, decoder: D) -> Self
+    where
+        R: Read,
+    {
+        let initial = BytesMut::from(reader.buffer());
+        let chunk_size = reader.capacity();
+        let mut framed = Self::with_initial_buffer(reader.into_inner(), decoder, initial);
+        framed.set_read_chunk_size(chunk_size);
+        framed
     }
-}
 
-pub struct Framed<R, W, D, E> {
-    reader: FramedRead<R, D>,
-    writer: FramedWrite<W, E>,
-}
+    /// Sets the number of bytes requested from the underlying `Read` object
+    /// on each read.
+    ///
+    /// Larger reads mean fewer system calls on fast or high-latency links,
+    /// while smaller ones keep memory use down. This is independent of the
+    /// buffer's initial capacity, which grows as needed to hold a frame.
+    ///
+    /// # Panics
+    /// If `n` is zero.
+    pub fn set_read_chunk_size(&mut self, n: usize) {
+        assert!(n > 0, "read chunk size must be greater than zero");
+        self.read_chunk_size = n;
+    }
 
-impl<R, W, D, E, I> Framed<R, W, D, E>
-where
-    R: Read,
-    W: Write,
-    D: Decoder<Item = I, Error = io::Error>,
-    E: Encoder<I>,
-{
-    pub fn new(reader: R, writer: W, decoder: D, encoder: E) -> Framed<R, W, D, E> {
-        Framed {
-            reader: FramedRead::new(reader, decoder),
-            writer: FramedWrite::new(writer, encoder),
+    /// Sets the read buffer capacity above which memory is given back.
+    ///
+    /// Decoding a large frame grows the read buffer, and without shrinking
+    /// it a long-lived connection would hold on to that memory forever.
+    /// After a frame is decoded, if the buffer capacity exceeds `threshold`
+    /// and the undecoded bytes would fit in the initial capacity, the buffer
+    /// is reallocated at its initial capacity. `None` disables shrinking.
+    ///
+    /// The default is 8 times the initial capacity.
+    pub fn set_shrink_threshold(&mut self, threshold: Option<usize>) {
+        self.shrink_threshold = threshold;
+    }
+
+    /// Sets the most bytes that may be buffered while waiting for a frame.
+    ///
+    /// A peer that never completes a frame, or announces a huge one, would
+    /// otherwise make the read buffer grow until memory runs out. Once the
+    /// buffer holds `max` bytes and the decoder still needs more,
+    /// `framed_read` returns an error of kind `ErrorKind::InvalidData`.
+    /// `None`, the default, places no limit on the buffer.
+    pub fn set_max_buffer_size(&mut self, max: Option<usize>) {
+        self.max_buffer_size = max;
+    }
+
+    /// Sets how many times in a row a `read` of the underlying object that
+    /// returns no bytes is retried. The default is 0, so the first empty
+    /// read is taken as EOF, as `Read` specifies.
+    ///
+    /// Some `Read` objects, such as a ring buffer that is momentarily
+    /// empty, return `Ok(0)` without having reached the end. With `max` set
+    /// each read of the `FramedRead` tries again up to `max` times, so these
+    /// do not end the stream. A stream that keeps returning nothing is not
+    /// read forever: once the retries run out the read fails with an error
+    /// of kind `ErrorKind::UnexpectedEof`, so that it can be told apart from
+    /// the stream really ending.
+    pub fn set_max_empty_reads(&mut self, max: usize) {
+        self.max_empty_reads = max;
+    }
+
+    /// Sets whether to check that the decoder makes progress.
+    /// The default is `false`.
+    ///
+    /// A buggy decoder that returns a frame without consuming any bytes
+    /// would make a caller draining the buffer loop forever, and one that
+    /// keeps asking for more bytes without looking at them can leave a
+    /// caller spinning. With this set, a decode that returns a frame but
+    /// removes nothing from the buffer, or that returns `Ok(None)` a second
+    /// time when the buffer has not changed since the first, fails with an
+    /// error saying the "decoder made no progress". This includes calling
+    /// [`FramedRead::peek_frame`] twice with no read in between. Like a
+    /// decode error, it leaves the `FramedRead` poisoned.
+    pub fn set_check_progress(&mut self, check: bool) {
+        self.check_progress = check;
+    }
+
+    fn check_buffer_size(&self) -> io::Result<()> {
+        match self.max_buffer_size {
+            Some(max) if self.buf.len() >= max => Err(Error::new(
+                ErrorKind::InvalidData,
+                "frame exceeds maximum buffer size",
+            )),
+            _ => Ok(()),
         }
     }
 
-    pub fn split(self) -> (FramedRead<R, D>, FramedWrite<W, E>) {
-        (self.reader, self.writer)
+    /// Clear the read buffer and any error from an earlier decode.
+    ///
+    /// Once the decoder returns an error the position of the next frame in
+    /// the buffer is unknown, so every subsequent read fails with an error
+    /// of kind `ErrorKind::Other` rather than decoding garbage. A caller
+    /// that knows how to get back in step with the stream, for example by
+    /// having the peer resend, can call `reset` and carry on reading.
+    pub fn reset(&mut self) {
+        self.buf.clear();
+        self.eof = false;
+        self.poisoned = false;
+        self.stalled_len = None;
+        self.seeded = false;
+    }
+
+    /// Returns `true` if an earlier decode error has left the `FramedRead`
+    /// unusable until [`FramedRead::reset`] is called.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    fn check_poisoned(&self) -> io::Result<()> {
+        if self.poisoned {
+            return Err(Error::other("FramedRead is unusable after a decode error"));
+        }
+        Ok(())
+    }
+
+    fn maybe_shrink(&mut self) {
+        let Some(threshold) = self.shrink_threshold else {
+            return;
+        };
+        if self.buf.capacity() > threshold && self.buf.len() <= self.capacity {
+            let mut buf = BytesMut::with_capacity(self.capacity);
+            buf.extend_from_slice(&self.buf);
+            self.buf = buf;
+        }
+    }
+
+    /// Returns a reference to the underlying `Read` object.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying `Read` object.
+    ///
+    /// Care should be taken not to read from it directly as this will
+    /// corrupt the stream of frames.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns a reference to the decoder.
+    pub fn decoder(&self) -> &D {
+        &self.decoder
+    }
+
+    /// Returns a mutable reference to the decoder, for example to change
+    /// its configuration between frames.
+    pub fn decoder_mut(&mut self) -> &mut D {
+        &mut self.decoder
+    }
+
+    /// Replace the decoder with another of the same type, returning the old
+    /// one.
+    ///
+    /// As with [`FramedRead::map_decoder`], bytes already read but not yet
+    /// decoded stay in the buffer and are decoded by the new decoder. This
+    /// only needs `&mut self`, so it suits a reconfigured decoder or one
+    /// with fresh state.
+    pub fn set_decoder(&mut self, decoder: D) -> D {
+        self.stalled_len = None;
+        mem::replace(&mut self.decoder, decoder)
+    }
+
+    /// Replace the decoder with one produced by `f`, possibly of a different
+    /// type.
+    ///
+    /// Bytes already read but not yet decoded stay in the buffer and are
+    /// decoded by the new decoder. This allows the frame format to change
+    /// part way through a stream, such as after a handshake, without losing
+    /// the start of the first frame in the new format.
+    pub fn map_decoder<D2, F>(self, f: F) -> FramedRead<R, D2>
+    where
+        F: FnOnce(D) -> D2,
+    {
+        FramedRead {
+            inner: self.inner,
+            decoder: f(self.decoder),
+            buf: self.buf,
+            capacity: self.capacity,
+            read_chunk_size: self.read_chunk_size,
+            shrink_threshold: self.shrink_threshold,
+            max_buffer_size: self.max_buffer_size,
+            max_empty_reads: self.max_empty_reads,
+            eof: self.eof,
+            poisoned: self.poisoned,
+            check_progress: self.check_progress,
+            stalled_len: self.stalled_len,
+            total_read: self.total_read,
+            seeded: self.seeded,
+        }
+    }
+
+    /// Call `f` with every frame once it has been decoded, for logging or
+    /// other diagnostics. See [`DecoderExt::inspect`].
+    ///
+    /// This wraps the decoder, so a `FramedRead` without a callback pays
+    /// nothing for the feature.
+    pub fn on_frame_decoded<F>(self, f: F) -> FramedRead<R, InspectDecoder<D, F>>
+    where
+        D: Decoder,
+        F: FnMut(&D::Item),
+    {
+        self.map_decoder(|decoder| decoder.inspect(f))
+    }
+
+    /// Copy every byte read from now on to `sink`, as well as decoding it.
+    ///
+    /// Bytes already in the read buffer are not copied. See [`Tee`].
+    pub fn tee<W: Write>(self, sink: W) -> FramedRead<Tee<R, W>, D> {
+        FramedRead {
+            inner: Tee::new(self.inner, sink),
+            decoder: self.decoder,
+            buf: self.buf,
+            capacity: self.capacity,
+            read_chunk_size: self.read_chunk_size,
+            shrink_threshold: self.shrink_threshold,
+            max_buffer_size: self.max_buffer_size,
+            max_empty_reads: self.max_empty_reads,
+            eof: self.eof,
+            poisoned: self.poisoned,
+            check_progress: self.check_progress,
+            stalled_len: self.stalled_len,
+            total_read: self.total_read,
+            seeded: self.seeded,
+        }
+    }
+
+    /// Returns a reference to the bytes that have been read but not yet decoded.
+    pub fn read_buffer(&self) -> &BytesMut {
+        &self.buf
+    }
+
+    /// Returns a mutable reference to the bytes that have been read but not
+    /// yet decoded.
+    ///
+    /// Any change made to the buffer is seen by the decoder on the next call
+    /// to `framed_read`, so removing or inserting bytes here alters the
+    /// stream of frames.
+    pub fn read_buffer_mut(&mut self) -> &mut BytesMut {
+        &mut self.buf
+    }
+
+    /// Returns the number of bytes that have been read but not yet decoded.
+    pub fn bytes_buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns the total number of bytes read from the underlying `Read`
+    /// object, whether or not they have been decoded yet.
+    ///
+    /// Bytes handed over with [`FramedRead::with_initial_buffer`] are not
+    /// counted, as they were not read by this `FramedRead`.
+    pub fn total_bytes_read(&self) -> u64 {
+        self.total_read
+    }
+
+    /// Consumes the `FramedRead`, returning the underlying `Read` object.
+    /// Any bytes that have been read but not yet decoded are lost; use
+    /// [`FramedRead::into_parts`] to keep them.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Consumes the `FramedRead`, returning the underlying `Read` object and
+    /// the bytes that have been read from it but not yet decoded.
+    ///
+    /// A reader usually reads ahead of the frame it is decoding, so after a
+    /// protocol upgrade the start of the new protocol may already be in the
+    /// buffer. Hand these bytes to whatever takes over the stream.
+    pub fn into_parts(self) -> (R, BytesMut) {
+        (self.inner, self.buf)
     }
 }
 
-/// Trait for reading frames
-pub trait FramedReader<I> {
-    fn framed_read(&mut self) -> io::Result<I>;
+pub struct FramedWrite<W, E> {
+    inner: W,
+    encoder: E,
+    buf: BytesMut,
+    flush_on_write: bool,
+    allow_empty_frames: bool,
+    heartbeat: Option<Heartbeat>,
+    rate_limit: Option<RateLimit>,
+    // The error that stopped the last failed `extend`
+    last_error: Option<io::Error>,
+    // Set when writing to the inner object failed, possibly part way
+    // through a frame
+    poisoned: bool,
+    // Bytes written to the inner object since creation
+    total_written: u64,
+    // Writes out the buffer when dropped, chosen where `W: Write` is known
+    drop_flush: fn(&mut W, &mut BytesMut),
 }
 
-/// Trait for writing frames
-pub trait FramedWriter<I> {
-    fn framed_write(&mut self, item: I) -> io::Result<()>;
+/// Best effort at writing out `buf` and flushing `inner`, for `Drop`.
+fn flush_on_drop<W: Write>(inner: &mut W, buf: &mut BytesMut) {
+    if inner.write_all(buf).is_ok() {
+        buf.clear();
+        let _ = inner.flush();
+    }
 }
 
-impl<I, R, D> FramedReader<I> for FramedRead<R, D>
-where
-    R: Read,
-    D: Decoder<Item = I, Error = io::Error>,
-{
-    fn framed_read(&mut self) -> io::Result<I> {
-        let mut src = [0u8; INITIAL_CAPACITY];
-        loop {
-            let bytes_read = self.inner.read(&mut src)?;
-            if bytes_read == 0 {
-                return Err(Error::new(
-                    ErrorKind::ConnectionReset,
-                    "Server connection reset",
-                ));
-            }
-            self.buf.extend_from_slice(&src[..bytes_read]);
-            match self.decoder.decode(&mut self.buf) {
-                Ok(Some(item)) => return Ok(item),
-                Ok(None) => continue,
-                Err(e) => return Err(e),
-            }
+// A pre-encoded frame sent when nothing else has been written for a while
+struct Heartbeat {
+    frame: Bytes,
+    interval: Duration,
+    clock: Box<dyn Clock + Send>,
+    last_write: Instant,
+}
+
+// A token bucket holding up to one second's worth of bytes
+struct RateLimit {
+    bytes_per_second: u64,
+    tokens: f64,
+    clock: Box<dyn Clock + Send>,
+    last_refill: Instant,
+}
+
+impl RateLimit {
+    /// Wait until `len` more bytes may be sent without exceeding the limit.
+    fn wait_for(&mut self, len: usize) {
+        let rate = self.bytes_per_second as f64;
+        let now = self.clock.now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(rate);
+        self.last_refill = now;
+
+        let len = len as f64;
+        if self.tokens < len {
+            let wait = Duration::from_secs_f64((len - self.tokens) / rate);
+            self.clock.sleep(wait);
+            self.last_refill = self.clock.now();
+            self.tokens = 0.0;
+        } else {
+            self.tokens -= len;
         }
     }
 }
 
-impl<I, W, E> FramedWriter<I> for FramedWrite<W, E>
-where
-    W: Write,
-    E: Encoder<I, Error = io::Error>,
-{
-    fn framed_write(&mut self, item: I) -> io::Result<()> {
-        let mut dst = BytesMut::with_capacity(INITIAL_CAPACITY);
-        self.encoder.encode(item, &mut dst)?;
-        self.inner.write(&dst[..])?;
-        self.inner.flush()
+impl<W: Write, E> FramedWrite<W, E> {
+    pub fn new(inner: W, encoder: E) -> Self {
+        Self::with_capacity(inner, encoder, INITIAL_CAPACITY)
     }
-}
 
-impl<R, W, D, E, I> FramedReader<I> for Framed<R, W, D, E>
-where
-    R: Read,
-    D: Decoder<Item = I, Error = io::Error>,
-{
-    fn framed_read(&mut self) -> io::Result<I> {
-        self.reader.framed_read()
+    /// Create a `FramedWrite` whose encode buffer starts with `capacity` bytes.
+    pub fn with_capacity(inner: W, encoder: E, capacity: usize) -> Self {
+        Self {
+            inner,
+            encoder,
+            buf: BytesMut::with_capacity(capacity),
+            flush_on_write: true,
+            allow_empty_frames: false,
+            heartbeat: None,
+            rate_limit: None,
+            last_error: None,
+            poisoned: false,
+            total_written: 0,
+            drop_flush: flush_on_drop::<W>,
+        }
     }
 }
 
-impl<R, W, D, E, I> FramedWriter<I> for Framed<R, W, D, E>
-where
-    W: Write,
-    E: Encoder<I, Error = io::Error>,
-{
-    fn framed_write(&mut self, item: I) -> io::Result<()> {
-        self.writer.framed_write(item)
+impl<W, E> FramedWrite<W, E> {
+    /// Sets whether `framed_write` flushes the underlying `Write` object
+    /// after writing each frame. The default is `true`.
+    ///
+    /// With this set to `false` each frame is still written out straight
+    /// away but flushing is left to an explicit call to `flush`, which
+    /// suits a `Write` object such as a `BufWriter` that gathers several
+    /// frames before sending them on.
+    pub fn set_flush_on_write(&mut self, flush_on_write: bool) {
+        self.flush_on_write = flush_on_write;
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use bytes::{Buf, BufMut};
-    use socket_server_mocker::{Instruction::*, ServerMocker};
+    /// Sets whether an encoder may produce a frame of zero bytes.
+    /// The default is `false`.
+    ///
+    /// An empty frame is usually a bug in the encoder, and silently sending
+    /// nothing would leave the peer waiting for a frame that never comes.
+    /// Unless allowed, a write whose encoder adds no bytes fails with an
+    /// error of kind `ErrorKind::InvalidData`.
+    pub fn set_allow_empty_frames(&mut self, allow: bool) {
+        self.allow_empty_frames = allow;
+    }
 
-    use std::net::TcpStream;
+    /// Returns a reference to the underlying `Write` object.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
 
-    use super::*;
+    /// Returns a mutable reference to the underlying `Write` object.
+    ///
+    /// Care should be taken not to write to it directly as this will
+    /// corrupt the stream of frames.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
 
-    #[derive(Debug, PartialEq)]
-    enum TestMsg {
-        U8(u8),
-        U16(u16),
-        Unrecognised,
+    /// Returns a reference to the encoder.
+    pub fn encoder(&self) -> &E {
+        &self.encoder
     }
 
-    struct TestCodec;
+    /// Returns a mutable reference to the encoder, for example to change
+    /// its configuration between frames.
+    pub fn encoder_mut(&mut self) -> &mut E {
+        &mut self.encoder
+    }
 
-    impl Decoder for TestCodec {
-        type Item = TestMsg;
-        type Error = io::Error;
+    /// Returns a reference to the write buffer, which holds encoded frames
+    /// that have not yet been written out.
+    ///
+    /// Every frame is encoded into this one buffer, which keeps its capacity
+    /// once flushed, so a steady stream of frames does not allocate once the
+    /// buffer has grown to fit the largest of them.
+    pub fn write_buffer(&self) -> &BytesMut {
+        &self.buf
+    }
 
-        fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-            if src.len() < 1 {
-                return Ok(None);
-            }
+    /// Returns the number of encoded bytes waiting in the write buffer to
+    /// be written out.
+    pub fn bytes_pending(&self) -> usize {
+        self.buf.len()
+    }
 
-            let msg_len = src[0];
-            if src.len() < msg_len as usize + 1 {
-                return Ok(None);
+    /// Returns the total number of bytes written to the underlying `Write`
+    /// object, including raw bytes written through the `Write`
+    /// implementation. Frames still in the write buffer are not counted.
+    pub fn total_bytes_written(&self) -> u64 {
+        self.total_written
+    }
+
+    /// Replace the encoder with one produced by `f`, possibly of a different
+    /// type. Frames already in the write buffer are kept and are sent ahead
+    /// of any frames encoded by the new encoder.
+    pub fn map_encoder<E2, F>(self, f: F) -> FramedWrite<W, E2>
+    where
+        F: FnOnce(E) -> E2,
+    {
+        let this = ManuallyDrop::new(self);
+        // SAFETY: every field is moved out exactly once and `this` is never
+        // used again, so nothing is dropped twice. Should `f` panic the
+        // other fields are leaked, which is safe.
+        unsafe {
+            FramedWrite {
+                inner: ptr::read(&this.inner),
+                encoder: f(ptr::read(&this.encoder)),
+                buf: ptr::read(&this.buf),
+                flush_on_write: this.flush_on_write,
+                allow_empty_frames: this.allow_empty_frames,
+                heartbeat: ptr::read(&this.heartbeat),
+                rate_limit: ptr::read(&this.rate_limit),
+                last_error: ptr::read(&this.last_error),
+                poisoned: this.poisoned,
+                total_written: this.total_written,
+                drop_flush: this.drop_flush,
             }
+        }
+    }
 
-            src.advance(1);
-            let mut msg = src.split_to(msg_len as usize);
+    /// Call `g` with every item once it has been encoded, along with the
+    /// bytes of its frame, for logging or other diagnostics. See
+    /// [`EncoderExt::inspect_encoded`].
+    ///
+    /// This wraps the encoder, so a `FramedWrite` without a callback pays
+    /// nothing for the feature.
+    pub fn on_frame_encoded<I, G>(self, g: G) -> FramedWrite<W, InspectEncoder<E, G>>
+    where
+        E: Encoder<I>,
+        G: FnMut(&I, &[u8]),
+        I: Clone,
+    {
+        self.map_encoder(|encoder| encoder.inspect_encoded(g))
+    }
 
-            match msg_len {
-                1 => {
-                    let val = msg.get_u8();
-                    Ok(Some(TestMsg::U8(val)))
-                }
-                2 => {
-                    let val = msg.get_u16();
-                    Ok(Some(TestMsg::U16(val)))
-                }
-                _ => Ok(Some(TestMsg::Unrecognised)),
-            }
+    /// Consumes the `FramedWrite`, returning the underlying `Write` object.
+    /// Any frames buffered by `framed_write_buffered` but not yet flushed
+    /// are lost; use [`FramedWrite::into_inner_flushed`] to keep them.
+    pub fn into_inner(self) -> W {
+        let mut this = ManuallyDrop::new(self);
+        // SAFETY: `inner` is moved out and every other field that owns
+        // anything is dropped, each exactly once, and `this` is never used
+        // again. Skipping `Drop` is what stops the buffer being written.
+        unsafe {
+            ptr::drop_in_place(&mut this.encoder);
+            ptr::drop_in_place(&mut this.buf);
+            ptr::drop_in_place(&mut this.heartbeat);
+            ptr::drop_in_place(&mut this.rate_limit);
+            ptr::drop_in_place(&mut this.last_error);
+            ptr::read(&this.inner)
         }
     }
 
-    impl Encoder<TestMsg> for TestCodec {
-        type Error = io::Error;
+    /// Encode `item` into the write buffer without writing it out.
+    ///
+    /// Buffered frames are sent, in order, by the next call to `flush` or
+    /// `framed_write`. This lets a burst of small frames go out in a single
+    /// write. If the encoder fails, nothing is added to the buffer. See
+    /// [`FramedWrite::set_allow_empty_frames`] for encoders that add nothing.
+    pub fn framed_write_buffered<I>(&mut self, item: I) -> Result<(), E::Error>
+    where
+        E: Encoder<I>,
+    {
+        let hint = self.encoder.size_hint(&item);
+        self.buffer_frame::<I, _>(hint, |encoder, buf| encoder.encode(item, buf))
+    }
 
-        fn encode(&mut self, item: TestMsg, dst: &mut BytesMut) -> Result<(), Self::Error> {
-            match item {
-                TestMsg::U8(val) => {
-                    dst.put_u8(1);
-                    dst.put_u8(val);
-                }
-                TestMsg::U16(val) => {
-                    dst.put_u8(2);
-                    dst.put_u16(val);
-                }
-                TestMsg::Unrecognised => {
-                    return Err(io::Error::new(io::ErrorKind::InvalidData, ""))
-                }
-            }
-            Ok(())
+    /// Encode a frame into the write buffer with `encode`, which calls one
+    /// of the encoder's methods.
+    fn buffer_frame<I, F>(&mut self, hint: Option<usize>, encode: F) -> Result<(), E::Error>
+    where
+        E: Encoder<I>,
+        F: FnOnce(&mut E, &mut BytesMut) -> Result<(), E::Error>,
+    {
+        if let Some(hint) = hint {
+            self.buf.reserve(hint);
+        }
+        let len = self.buf.len();
+        if let Err(e) = encode(&mut self.encoder, &mut self.buf) {
+            self.buf.truncate(len);
+            return Err(e);
+        }
+        if self.buf.len() == len && !self.allow_empty_frames {
+            return Err(
+                Error::new(ErrorKind::InvalidData, "encoder produced an empty frame").into(),
+            );
         }
+        Ok(())
     }
 
-    #[test]
-    fn reader_valid_u8() {
-        let r = vec![1u8, 128];
-        let mut framed = FramedRead::new(&r[..], TestCodec);
-        let data = framed.framed_read().unwrap();
-        assert_eq!(data, TestMsg::U8(128));
+    /// Returns `true` if writing to the underlying `Write` object has failed.
+    ///
+    /// The failed write may have sent part of a frame, in which case the
+    /// peer can no longer find where the next frame starts unless the rest
+    /// is sent with [`FramedWrite::resume_write`]. Writing is still allowed,
+    /// as some errors, such as `ErrorKind::Interrupted`, are harmless.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
     }
 
-    #[test]
-    fn reader_valid_u16() {
-        let r = vec![2u8, 1, 128];
-        let mut framed = FramedRead::new(&r[..], TestCodec);
-        let data = framed.framed_read().unwrap();
-        assert_eq!(data, TestMsg::U16(2u16.pow(8) + 128));
+    /// Discard any frames in the write buffer and clear the poisoned state.
+    pub fn reset(&mut self) {
+        self.buf.clear();
+        self.poisoned = false;
     }
 
-    #[test]
-    fn read_unrecognised() {
-        let r = vec![3u8, 1, 128, 0];
-        let mut framed = FramedRead::new(&r[..], TestCodec);
-        let data = framed.framed_read().unwrap();
-        assert_eq!(data, TestMsg::Unrecognised);
+    /// Returns the error that stopped an earlier call to `extend`, if any.
+    /// See the [`Extend`] impl.
+    pub fn last_error(&self) -> Option<&io::Error> {
+        self.last_error.as_ref()
     }
 
-    #[test]
-    fn read_zero_length() {
-        let r: Vec<u8> = Vec::new();
-        let mut framed = FramedRead::new(&r[..], TestCodec);
-        let data = framed.framed_read();
-        assert!(data.is_err());
+    /// Takes the error that stopped an earlier call to `extend`, leaving
+    /// `None` in its place.
+    pub fn take_last_error(&mut self) -> Option<io::Error> {
+        self.last_error.take()
     }
 
-    #[test]
-    fn write_valid_u8() {
-        let mut buf = vec![];
-        let mut framed = FramedWrite::new(&mut buf, TestCodec);
-        framed.framed_write(TestMsg::U8(12)).ok();
-        assert_eq!(buf, vec![1, 12]);
+    /// Send `item` whenever `interval` passes without any frame being
+    /// written, to keep an idle connection alive.
+    ///
+    /// Nothing is sent in the background: call
+    /// [`FramedWrite::maybe_send_heartbeat`] regularly, for example each
+    /// time round an application's main loop. The item is encoded once,
+    /// here, and the same bytes are sent for every heartbeat.
+    pub fn set_heartbeat<I>(&mut self, item: I, interval: Duration) -> Result<(), E::Error>
+    where
+        E: Encoder<I>,
+    {
+        self.set_heartbeat_with_clock(item, interval, SystemClock)
     }
 
-    #[test]
-    fn write_valid_u16() {
-        let mut buf = vec![];
-        let mut framed = FramedWrite::new(&mut buf, TestCodec);
-        framed.framed_write(TestMsg::U16(1234)).ok();
-        assert_eq!(buf, vec![2, 4, 210]);
+    /// As [`FramedWrite::set_heartbeat`] but measuring time with `clock`.
+    pub fn set_heartbeat_with_clock<I, C>(
+        &mut self,
+        item: I,
+        interval: Duration,
+        clock: C,
+    ) -> Result<(), E::Error>
+    where
+        E: Encoder<I>,
+        C: Clock + Send + 'static,
+    {
+        let mut frame = BytesMut::new();
+        self.encoder.encode(item, &mut frame)?;
+        self.heartbeat = Some(Heartbeat {
+            frame: frame.freeze(),
+            interval,
+            last_write: clock.now(),
+            clock: Box::new(clock),
+        });
+        Ok(())
     }
 
-    #[test]
-    fn invalid_write() {
-        let mut buf = vec![];
-        let mut framed = FramedWrite::new(&mut buf, TestCodec);
-        let response = framed.framed_write(TestMsg::Unrecognised);
-        assert!(response.is_err());
+    /// Stop sending heartbeats.
+    pub fn clear_heartbeat(&mut self) {
+        self.heartbeat = None;
     }
 
-    #[test]
-    fn framed() {
-        let reader = vec![2u8, 4, 210];
-        let mut writer = vec![];
-        let mut framed = Framed::new(&reader[..], &mut writer, TestCodec, TestCodec);
-        framed.framed_write(TestMsg::U16(1234)).ok();
-        let msg = framed.framed_read().unwrap();
-        assert_eq!(writer, vec![2u8, 4, 210]);
-        assert_eq!(msg, TestMsg::U16(1234));
+    /// Limit the rate at which frames are written to `bytes_per_second`,
+    /// or remove the limit with `None`, the default.
+    ///
+    /// **This blocks.** A write that would go over the limit sleeps the
+    /// calling thread for just long enough to stay under it before writing.
+    /// Up to one second's worth of bytes may be sent in a burst after the
+    /// connection has been idle. Bytes written directly through the `Write`
+    /// impl are not counted.
+    ///
+    /// # Panics
+    /// If `bytes_per_second` is zero.
+    pub fn set_rate_limit(&mut self, bytes_per_second: Option<u64>) {
+        self.set_rate_limit_with_clock(bytes_per_second, SystemClock);
     }
 
-    #[test]
-    fn framed_over_tcp() {
-        let test_buf = vec![2u8, 25, 143];
+    /// As [`FramedWrite::set_rate_limit`] but measuring time, and sleeping,
+    /// with `clock`.
+    pub fn set_rate_limit_with_clock<C>(&mut self, bytes_per_second: Option<u64>, clock: C)
+    where
+        C: Clock + Send + 'static,
+    {
+        self.rate_limit = bytes_per_second.map(|bytes_per_second| {
+            assert!(bytes_per_second > 0, "rate limit must be greater than zero");
+            RateLimit {
+                bytes_per_second,
+                tokens: bytes_per_second as f64,
+                last_refill: clock.now(),
+                clock: Box::new(clock),
+            }
+        });
+    }
+}
 
-        let tcp_server_mocker = ServerMocker::tcp_with_port(35642).unwrap();
-        let rx = TcpStream::connect("127.0.0.1:35642").unwrap();
-        let tx = rx.try_clone().unwrap();
+/// Dropping a `FramedWrite` makes a best effort at writing out any frames
+/// still in the write buffer and flushing the underlying `Write` object.
+///
+/// Any error is ignored, as `drop` cannot return one, and a blocking stream
+/// may block the drop. Call `flush`, or `shutdown_write` for a stream that
+/// supports it, before dropping a `FramedWrite` to find out whether the
+/// frames were sent. Nothing is written once a write has failed and left
+/// the `FramedWrite` poisoned.
+impl<W, E> Drop for FramedWrite<W, E> {
+    fn drop(&mut self) {
+        if !self.poisoned {
+            (self.drop_flush)(&mut self.inner, &mut self.buf);
+        }
+    }
+}
 
-        let mut framed = Framed::new(rx, tx, TestCodec, TestCodec);
+impl<W: Write, E> FramedWrite<W, E> {
+    /// Write out all buffered frames and flush the underlying `Write` object.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_buf()?;
+        self.inner.flush()
+    }
 
-        tcp_server_mocker
-            .add_mock_instructions(vec![
+    /// Write out all buffered frames and flush, then return the underlying
+    /// `Write` object.
+    ///
+    /// Unlike dropping the `FramedWrite`, this reports whether the frames
+    /// were sent. Should the flush fail the `FramedWrite` is dropped along
+    /// with any frames that could not be written.
+    pub fn into_inner_flushed(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.into_inner())
+    }
+
+    /// Finish sending frames after a write failed part way through.
+    ///
+    /// When writing to the underlying `Write` object fails, the bytes it
+    /// had not yet accepted stay in the write buffer, so a frame may be
+    /// half on the wire. If the error was transient, such as a timeout or
+    /// `ErrorKind::WouldBlock`, call this to write out the rest, flushing as
+    /// configured by `set_flush_on_write`. Each byte is sent exactly once.
+    /// Once everything has been written the `FramedWrite` is no longer
+    /// poisoned.
+    pub fn resume_write(&mut self) -> io::Result<()> {
+        self.write_frames()?;
+        self.poisoned = false;
+        Ok(())
+    }
+
+    /// Write out all buffered frames, flushing as configured by
+    /// `set_flush_on_write`.
+    fn write_frames(&mut self) -> io::Result<()> {
+        if self.flush_on_write {
+            self.flush()
+        } else {
+            self.write_buf()
+        }
+    }
+
+    fn write_buf(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            if let Some(rate_limit) = &mut self.rate_limit {
+                rate_limit.wait_for(self.buf.len());
+            }
+            // As `write_all`, but dropping each part from the buffer once it
+            // has been written so that only the rest is sent on a retry
+            let capacity = self.buf.capacity();
+            while !self.buf.is_empty() {
+                match self.inner.write(&self.buf) {
+                    Ok(0) => {
+                        self.poisoned = true;
+                        return Err(Error::new(
+                            ErrorKind::WriteZero,
+                            "failed to write whole frame",
+                        ));
+                    }
+                    Ok(n) => {
+                        self.buf.advance(n);
+                        self.total_written += n as u64;
+                    }
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        self.poisoned = true;
+                        return Err(e);
+                    }
+                }
+            }
+            // Move back to the start of the now empty buffer rather than
+            // allocating again
+            self.buf.reserve(capacity);
+            if let Some(heartbeat) = &mut self.heartbeat {
+                heartbeat.last_write = heartbeat.clock.now();
+            }
+        }
+        Ok(())
+    }
+
+    /// Send the heartbeat frame if the heartbeat interval has passed since
+    /// the last frame was written. Returns `true` if one was sent.
+    ///
+    /// Does nothing unless a heartbeat has been set with
+    /// [`FramedWrite::set_heartbeat`].
+    pub fn maybe_send_heartbeat(&mut self) -> io::Result<bool> {
+        let frame = match &self.heartbeat {
+            Some(hb) if hb.clock.now().duration_since(hb.last_write) >= hb.interval => {
+                hb.frame.clone()
+            }
+            _ => return Ok(false),
+        };
+        self.buf.extend_from_slice(&frame);
+        self.write_frames()?;
+        Ok(true)
+    }
+
+    /// Write a frame using [`Encoder::encode_vectored`] and a vectored
+    /// write, so that the parts of the frame are written out without first
+    /// being copied into the write buffer.
+    ///
+    /// This suits frames with a large payload, such as a proxy forwarding
+    /// the payloads it receives, from codecs like `LengthDelimitedCodec`
+    /// that hand over the payload as it is. For an encoder that does not
+    /// override `encode_vectored` this is the same as `framed_write`, and a
+    /// `Write` object without vectored writes simply writes the parts one
+    /// at a time. Frames waiting in the write buffer are written out first.
+    pub fn framed_write_vectored<I>(&mut self, item: I) -> Result<(), E::Error>
+    where
+        E: Encoder<I>,
+    {
+        let mut bufs = Vec::new();
+        self.encoder.encode_vectored(item, &mut bufs)?;
+        let len = bufs.iter().map(Bytes::len).sum();
+        if len == 0 && !self.allow_empty_frames {
+            return Err(
+                Error::new(ErrorKind::InvalidData, "encoder produced an empty frame").into(),
+            );
+        }
+
+        self.write_buf()?;
+        if let Some(rate_limit) = &mut self.rate_limit {
+            rate_limit.wait_for(len);
+        }
+        if let Err(e) = write_all_vectored(&mut self.inner, &bufs, &mut self.total_written) {
+            self.poisoned = true;
+            return Err(e.into());
+        }
+        if let Some(heartbeat) = &mut self.heartbeat {
+            heartbeat.last_write = heartbeat.clock.now();
+        }
+        if self.flush_on_write {
+            if let Err(e) = self.inner.flush() {
+                self.poisoned = true;
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a frame without blocking, for use with a non-blocking `Write`
+    /// object such as a `TcpStream` after `set_nonblocking(true)`.
+    ///
+    /// The frame is added to the write buffer and as much of the buffer as
+    /// the underlying `write` accepts is written out. Returns `Ok(true)`
+    /// once everything has been written and flushed, or `Ok(false)` if the
+    /// underlying object returned an error of kind `ErrorKind::WouldBlock`
+    /// first. The bytes not yet written stay in the buffer, so no partial
+    /// frame is left on the wire; call [`FramedWrite::try_flush`] when the
+    /// stream is writable again to send them.
+    ///
+    /// The rate limit, if one is set, is not applied.
+    pub fn try_framed_write<I>(&mut self, item: I) -> Result<bool, E::Error>
+    where
+        E: Encoder<I>,
+    {
+        self.framed_write_buffered(item)?;
+        Ok(self.try_flush()?)
+    }
+
+    /// Write out as much of the write buffer as possible without blocking,
+    /// then flush the underlying `Write` object. Returns `Ok(true)` if the
+    /// buffer is now empty and the flush succeeded, or `Ok(false)` if the
+    /// underlying object returned an error of kind `ErrorKind::WouldBlock`.
+    /// See [`FramedWrite::try_framed_write`].
+    pub fn try_flush(&mut self) -> io::Result<bool> {
+        while !self.buf.is_empty() {
+            match self.inner.write(&self.buf) {
+                Ok(0) => {
+                    self.poisoned = true;
+                    return Err(Error::new(
+                        ErrorKind::WriteZero,
+                        "failed to write whole frame",
+                    ));
+                }
+                Ok(n) => {
+                    self.buf.advance(n);
+                    self.total_written += n as u64;
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                Err(e) => {
+                    self.poisoned = true;
+                    return Err(e);
+                }
+            }
+            if self.buf.is_empty() {
+                if let Some(heartbeat) = &mut self.heartbeat {
+                    heartbeat.last_write = heartbeat.clock.now();
+                }
+            }
+        }
+        match self.inner.flush() {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write a frame as `framed_write` does, returning the number of bytes
+    /// the encoded frame occupies on the wire.
+    pub fn framed_write_len<I>(&mut self, item: I) -> Result<usize, E::Error>
+    where
+        E: Encoder<I>,
+    {
+        let len = self.buf.len();
+        self.framed_write_buffered(item)?;
+        let frame_len = self.buf.len() - len;
+        self.write_frames()?;
+        Ok(frame_len)
+    }
+}
+
+/// As `Write::write_all` but for a frame in several parts, adding the bytes
+/// written to `written`.
+fn write_all_vectored<W: Write>(
+    inner: &mut W,
+    bufs: &[Bytes],
+    written: &mut u64,
+) -> io::Result<()> {
+    let mut slices: Vec<IoSlice> = bufs
+        .iter()
+        .filter(|buf| !buf.is_empty())
+        .map(|buf| IoSlice::new(buf))
+        .collect();
+    let mut slices = &mut slices[..];
+    while !slices.is_empty() {
+        match inner.write_vectored(slices) {
+            Ok(0) => {
+                return Err(Error::new(
+                    ErrorKind::WriteZero,
+                    "failed to write whole frame",
+                ))
+            }
+            Ok(n) => {
+                IoSlice::advance_slices(&mut slices, n);
+                *written += n as u64;
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+impl<D: Decoder> FramedRead<StdinLock<'static>, D> {
+    /// Create a `FramedRead` over the locked standard input, for a program
+    /// that reads frames from stdin.
+    ///
+    /// Standard input stays locked until the `FramedRead` is dropped. The
+    /// lock buffers what it reads as well, which costs an extra copy of each
+    /// byte but nothing more.
+    ///
+    /// ```no_run
+    /// use framous::codec::LinesCodec;
+    /// use framous::{FramedRead, FramedWrite, FramedWriter};
+    ///
+    /// // Upper-case each line, as a filter in a pipeline
+    /// let mut output = FramedWrite::stdout(LinesCodec::new());
+    /// for line in FramedRead::stdin(LinesCodec::new()) {
+    ///     output.framed_write(line?.to_uppercase())?;
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn stdin(decoder: D) -> Self {
+        Self::new(io::stdin().lock(), decoder)
+    }
+}
+
+impl<E> FramedWrite<StdoutLock<'static>, E> {
+    /// Create a `FramedWrite` over the locked standard output, for a program
+    /// that writes frames to stdout.
+    ///
+    /// Standard output stays locked until the `FramedWrite` is dropped, so
+    /// `println!` from the same program waits until then. As stdout is line
+    /// buffered, leave `flush_on_write` set unless frames are flushed some
+    /// other way.
+    pub fn stdout(encoder: E) -> Self {
+        Self::new(io::stdout().lock(), encoder)
+    }
+}
+
+impl<R, D: Decoder> From<(R, D)> for FramedRead<R, D> {
+    fn from((inner, decoder): (R, D)) -> Self {
+        FramedRead::new(inner, decoder)
+    }
+}
+
+impl<W: Write, E> From<(W, E)> for FramedWrite<W, E> {
+    fn from((inner, encoder): (W, E)) -> Self {
+        FramedWrite::new(inner, encoder)
+    }
+}
+
+impl<R, W: Write, D: Decoder, E> From<(R, W, D, E)> for Framed<R, W, D, E> {
+    fn from((reader, writer, decoder, encoder): (R, W, D, E)) -> Self {
+        Framed {
+            reader: FramedRead::new(reader, decoder),
+            writer: FramedWrite::new(writer, encoder),
+        }
+    }
+}
+
+/// Writes each item as `framed_write` does, so that a `FramedWrite` can be
+/// the target of [`Iterator::collect`]-style code such as
+/// `writer.extend(messages)`.
+///
+/// `extend` cannot return an error, so the first failure stops it, leaving
+/// the rest of the items unsent, and is kept for
+/// [`FramedWrite::last_error`] or [`FramedWrite::take_last_error`]. The
+/// error stays there until taken, even if a later `extend` succeeds, so
+/// check it after each call. Only available for encoders whose error type
+/// is `io::Error`, as it is for all the built-in codecs.
+impl<I, W, E> Extend<I> for FramedWrite<W, E>
+where
+    W: Write,
+    E: Encoder<I, Error = io::Error>,
+{
+    fn extend<T: IntoIterator<Item = I>>(&mut self, iter: T) {
+        for item in iter {
+            if let Err(e) = self.framed_write(item) {
+                self.last_error = Some(e);
+                return;
+            }
+        }
+    }
+}
+
+/// Writes raw bytes straight to the underlying `Write` object, bypassing the
+/// encoder.
+///
+/// This is an escape hatch for protocols with unframed parts, such as a
+/// handshake sent before the first frame. Nothing written this way is
+/// framed. Frames waiting in the write buffer are written out first so the
+/// bytes on the wire stay in order, and `flush` is [`FramedWrite::flush`].
+impl<W: Write, E> Write for FramedWrite<W, E> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_buf()?;
+        let n = self.inner.write(buf)?;
+        self.total_written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        FramedWrite::flush(self)
+    }
+}
+
+pub struct Framed<R, W, D, E> {
+    reader: FramedRead<R, D>,
+    writer: FramedWrite<W, E>,
+}
+
+impl<R, W, D, E, I> Framed<R, W, D, E>
+where
+    R: Read,
+    W: Write,
+    D: Decoder<Item = I>,
+    E: Encoder<I>,
+{
+    pub fn new(reader: R, writer: W, decoder: D, encoder: E) -> Framed<R, W, D, E> {
+        Framed {
+            reader: FramedRead::new(reader, decoder),
+            writer: FramedWrite::new(writer, encoder),
+        }
+    }
+
+    /// Create a `Framed` whose read and write buffers start with
+    /// `read_capacity` and `write_capacity` bytes respectively.
+    /// See [`FramedRead::with_capacity`] and [`FramedWrite::with_capacity`].
+    pub fn with_capacities(
+        reader: R,
+        writer: W,
+        decoder: D,
+        encoder: E,
+        read_capacity: usize,
+        write_capacity: usize,
+    ) -> Framed<R, W, D, E> {
+        Framed {
+            reader: FramedRead::with_capacity(reader, decoder, read_capacity),
+            writer: FramedWrite::with_capacity(writer, encoder, write_capacity),
+        }
+    }
+
+    /// Create a `Framed` whose read buffer starts out holding `initial`.
+    /// See [`FramedRead::with_initial_buffer`].
+    pub fn with_initial_buffer(
+        reader: R,
+        writer: W,
+        decoder: D,
+        encoder: E,
+        initial: BytesMut,
+    ) -> Framed<R, W, D, E> {
+        Framed {
+            reader: FramedRead::with_initial_buffer(reader, decoder, initial),
+            writer: FramedWrite::new(writer, encoder),
+        }
+    }
+}
+
+impl<R, W, C> Framed<R, W, C, C>
+where
+    R: Read,
+    W: Write,
+    C: Decoder + Encoder<C::Item> + Clone,
+{
+    /// Create a `Framed` using a single codec that is both `Decoder` and
+    /// `Encoder`. The codec is cloned so that each half has its own copy.
+    pub fn with_codec(reader: R, writer: W, codec: C) -> Self {
+        Framed {
+            reader: FramedRead::new(reader, codec.clone()),
+            writer: FramedWrite::new(writer, codec),
+        }
+    }
+}
+
+impl<D: Decoder, E> Framed<TcpStream, TcpStream, D, E> {
+    /// Create a `Framed` from a `TcpStream`, using `try_clone` to get a
+    /// second handle for the writing half.
+    pub fn from_tcp(stream: TcpStream, decoder: D, encoder: E) -> io::Result<Self> {
+        let writer = stream.try_clone()?;
+        Ok(Framed {
+            reader: FramedRead::new(stream, decoder),
+            writer: FramedWrite::new(writer, encoder),
+        })
+    }
+
+    /// Create a `Framed` from a `TcpStream` as [`Framed::from_tcp`] does,
+    /// first applying `options` to the stream.
+    ///
+    /// Socket options belong to the connection rather than to a handle, so
+    /// they apply to both halves.
+    pub fn from_tcp_with(
+        stream: TcpStream,
+        decoder: D,
+        encoder: E,
+        options: TcpOptions,
+    ) -> io::Result<Self> {
+        options.apply(&stream)?;
+        Self::from_tcp(stream, decoder, encoder)
+    }
+
+    /// Split a `TcpStream` into a `FramedRead` and a `FramedWrite` over the
+    /// same connection. Both halves are `Send` as long as the codecs are, so
+    /// each can be moved to its own thread.
+    pub fn split_tcp(
+        stream: TcpStream,
+        decoder: D,
+        encoder: E,
+    ) -> io::Result<(FramedRead<TcpStream, D>, FramedWrite<TcpStream, E>)> {
+        Ok(Self::from_tcp(stream, decoder, encoder)?.split())
+    }
+}
+
+impl<C> Framed<TcpStream, TcpStream, C, C>
+where
+    C: Decoder + Encoder<C::Item> + Clone,
+{
+    /// Connect to `addr` and create a `Framed` over the connection, using
+    /// a single codec for both halves as [`Framed::with_codec`] does.
+    ///
+    /// This is `TcpStream::connect` followed by [`Framed::from_tcp`].
+    pub fn connect<A: ToSocketAddrs>(addr: A, codec: C) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        Self::from_tcp(stream, codec.clone(), codec)
+    }
+
+    /// Create a `Framed` over a stream returned by `TcpListener::accept`,
+    /// using a single codec for both halves. See [`FramedListener`] to
+    /// accept a stream of such connections.
+    pub fn from_accepted(stream: TcpStream, codec: C) -> io::Result<Self> {
+        Self::from_tcp(stream, codec.clone(), codec)
+    }
+}
+
+/// A `TcpListener` that hands out each connection it accepts as a
+/// `Framed`, with its own clone of a codec.
+///
+/// Iterating over a `FramedListener` accepts connections one after another,
+/// as `TcpListener::incoming` does, and never returns `None`.
+///
+/// ```no_run
+/// use framous::codec::LinesCodec;
+/// use framous::{FramedListener, FramedReader};
+///
+/// let listener = FramedListener::bind("127.0.0.1:8080", LinesCodec::new())?;
+/// for framed in listener {
+///     let mut framed = framed?;
+///     println!("{}", framed.framed_read()?);
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct FramedListener<C> {
+    listener: TcpListener,
+    codec: C,
+}
+
+impl<C> FramedListener<C>
+where
+    C: Decoder + Encoder<C::Item> + Clone,
+{
+    pub fn new(listener: TcpListener, codec: C) -> Self {
+        Self { listener, codec }
+    }
+
+    /// Create a `FramedListener` bound to `addr`. See `TcpListener::bind`.
+    pub fn bind<A: ToSocketAddrs>(addr: A, codec: C) -> io::Result<Self> {
+        Ok(Self::new(TcpListener::bind(addr)?, codec))
+    }
+
+    /// Wait for a connection and return it as a `Framed` along with the
+    /// address of the peer.
+    pub fn accept(&self) -> io::Result<(Framed<TcpStream, TcpStream, C, C>, SocketAddr)> {
+        let (stream, addr) = self.listener.accept()?;
+        Ok((Framed::from_accepted(stream, self.codec.clone())?, addr))
+    }
+
+    /// Returns the address the listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Returns a reference to the underlying `TcpListener`.
+    pub fn get_ref(&self) -> &TcpListener {
+        &self.listener
+    }
+
+    /// Consumes the `FramedListener`, returning the underlying
+    /// `TcpListener`.
+    pub fn into_inner(self) -> TcpListener {
+        self.listener
+    }
+}
+
+impl<C> Iterator for FramedListener<C>
+where
+    C: Decoder + Encoder<C::Item> + Clone,
+{
+    type Item = io::Result<Framed<TcpStream, TcpStream, C, C>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.accept().map(|(framed, _)| framed))
+    }
+}
+
+/// Connection-wide socket options for [`Framed::from_tcp_with`].
+///
+/// Options that are not set leave the stream as it is.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpOptions {
+    read_timeout: Option<Option<Duration>>,
+    write_timeout: Option<Option<Duration>>,
+    nodelay: Option<bool>,
+}
+
+impl TcpOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the timeout for every read. See `TcpStream::set_read_timeout`.
+    pub fn read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout for every write. See `TcpStream::set_write_timeout`.
+    pub fn write_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.write_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets whether small frames are sent straight away rather than being
+    /// held back by Nagle's algorithm. See `TcpStream::set_nodelay`.
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = Some(nodelay);
+        self
+    }
+
+    /// Apply the options to `stream`.
+    pub fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        if let Some(timeout) = self.read_timeout {
+            stream.set_read_timeout(timeout)?;
+        }
+        if let Some(timeout) = self.write_timeout {
+            stream.set_write_timeout(timeout)?;
+        }
+        if let Some(nodelay) = self.nodelay {
+            stream.set_nodelay(nodelay)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S, C> Framed<Shared<S>, Shared<S>, Shared<C>, Shared<C>>
+where
+    S: Read + Write,
+    C: Decoder,
+{
+    /// Create a `Framed` from a single stream that is both `Read` and
+    /// `Write`, using a single codec that is both `Decoder` and `Encoder`.
+    ///
+    /// The stream and codec are shared between the two halves rather than
+    /// cloned, so this suits single-threaded use. Unlike building a `Framed`
+    /// from two halves of a `TcpStream`, no `try_clone` is needed.
+    pub fn from_stream(stream: S, codec: C) -> Self {
+        let stream = Shared::new(stream);
+        let codec = Shared::new(codec);
+        Framed {
+            reader: FramedRead::new(stream.clone(), codec.clone()),
+            writer: FramedWrite::new(stream, codec),
+        }
+    }
+}
+
+impl<R, W, D, E> Framed<R, W, D, E> {
+    pub fn split(self) -> (FramedRead<R, D>, FramedWrite<W, E>) {
+        (self.reader, self.writer)
+    }
+
+    /// Consumes the `Framed`, keeping only the reading half.
+    ///
+    /// The writing half is dropped, which makes a best effort at writing out
+    /// any frames still in its buffer, so flush first to find out whether
+    /// they were sent. Dropping a stream does not shut it down while other
+    /// handles to it remain: a `TcpStream` made with `try_clone` keeps the
+    /// connection open for reading, and the peer is only told that nothing
+    /// more will be written if [`Framed::shutdown_write`] is called first.
+    pub fn into_framed_read(self) -> FramedRead<R, D> {
+        self.reader
+    }
+
+    /// Consumes the `Framed`, keeping only the writing half.
+    ///
+    /// Bytes read ahead into the reader's buffer are dropped with it. As
+    /// for [`Framed::into_framed_read`], dropping the reading stream does
+    /// not close a `TcpStream` that still has another handle.
+    pub fn into_framed_write(self) -> FramedWrite<W, E> {
+        self.writer
+    }
+
+    /// Put the halves returned by [`Framed::split`] back together.
+    ///
+    /// Both buffers and codecs are kept as they are, so bytes read ahead by
+    /// the reader and frames waiting in the writer are not lost. The halves
+    /// are not checked to belong together: any `FramedRead` and
+    /// `FramedWrite` may be joined, such as the two halves made from a
+    /// `TcpStream` and its `try_clone`.
+    pub fn reunite(reader: FramedRead<R, D>, writer: FramedWrite<W, E>) -> Self {
+        Framed { reader, writer }
+    }
+
+    /// Returns references to the underlying `Read` and `Write` objects.
+    pub fn get_ref(&self) -> (&R, &W) {
+        (self.reader.get_ref(), self.writer.get_ref())
+    }
+
+    /// Returns mutable references to the underlying `Read` and `Write` objects.
+    pub fn get_mut(&mut self) -> (&mut R, &mut W) {
+        (self.reader.get_mut(), self.writer.get_mut())
+    }
+
+    /// Returns a reference to the decoder.
+    pub fn decoder(&self) -> &D {
+        self.reader.decoder()
+    }
+
+    /// Returns a mutable reference to the decoder.
+    pub fn decoder_mut(&mut self) -> &mut D {
+        self.reader.decoder_mut()
+    }
+
+    /// Replace the decoder, returning the old one.
+    /// See [`FramedRead::set_decoder`].
+    pub fn set_decoder(&mut self, decoder: D) -> D {
+        self.reader.set_decoder(decoder)
+    }
+
+    /// Returns a reference to the encoder.
+    pub fn encoder(&self) -> &E {
+        self.writer.encoder()
+    }
+
+    /// Returns a mutable reference to the encoder.
+    pub fn encoder_mut(&mut self) -> &mut E {
+        self.writer.encoder_mut()
+    }
+
+    /// Returns a reference to the bytes that have been read but not yet decoded.
+    pub fn read_buffer(&self) -> &BytesMut {
+        self.reader.read_buffer()
+    }
+
+    /// Returns a mutable reference to the bytes that have been read but not
+    /// yet decoded. See [`FramedRead::read_buffer_mut`].
+    pub fn read_buffer_mut(&mut self) -> &mut BytesMut {
+        self.reader.read_buffer_mut()
+    }
+
+    /// Returns a reference to the write buffer.
+    /// See [`FramedWrite::write_buffer`].
+    pub fn write_buffer(&self) -> &BytesMut {
+        self.writer.write_buffer()
+    }
+
+    /// Returns the number of bytes read but not yet decoded.
+    /// See [`FramedRead::bytes_buffered`].
+    pub fn bytes_buffered(&self) -> usize {
+        self.reader.bytes_buffered()
+    }
+
+    /// Returns the number of encoded bytes not yet written out.
+    /// See [`FramedWrite::bytes_pending`].
+    pub fn bytes_pending(&self) -> usize {
+        self.writer.bytes_pending()
+    }
+
+    /// Returns the total number of bytes read.
+    /// See [`FramedRead::total_bytes_read`].
+    pub fn total_bytes_read(&self) -> u64 {
+        self.reader.total_bytes_read()
+    }
+
+    /// Returns the total number of bytes written.
+    /// See [`FramedWrite::total_bytes_written`].
+    pub fn total_bytes_written(&self) -> u64 {
+        self.writer.total_bytes_written()
+    }
+
+    /// Encode `item` into the write buffer without writing it out.
+    /// See [`FramedWrite::framed_write_buffered`].
+    pub fn framed_write_buffered<I>(&mut self, item: I) -> Result<(), E::Error>
+    where
+        E: Encoder<I>,
+    {
+        self.writer.framed_write_buffered(item)
+    }
+
+    /// Write a frame with a vectored write.
+    /// See [`FramedWrite::framed_write_vectored`].
+    pub fn framed_write_vectored<I>(&mut self, item: I) -> Result<(), E::Error>
+    where
+        W: Write,
+        E: Encoder<I>,
+    {
+        self.writer.framed_write_vectored(item)
+    }
+
+    /// Write a frame without blocking.
+    /// See [`FramedWrite::try_framed_write`].
+    pub fn try_framed_write<I>(&mut self, item: I) -> Result<bool, E::Error>
+    where
+        W: Write,
+        E: Encoder<I>,
+    {
+        self.writer.try_framed_write(item)
+    }
+
+    /// Write out buffered frames without blocking.
+    /// See [`FramedWrite::try_flush`].
+    pub fn try_flush(&mut self) -> io::Result<bool>
+    where
+        W: Write,
+    {
+        self.writer.try_flush()
+    }
+
+    /// Consumes the `Framed`, returning the underlying `Read` and `Write` objects.
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader.into_inner(), self.writer.into_inner())
+    }
+}
+
+impl<R, W: Write, D, E> Framed<R, W, D, E> {
+    /// Write out all buffered frames and flush the underlying `Write` object.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Write out all buffered frames and shut down the writing half.
+    /// See [`FramedWrite::shutdown_write`].
+    pub fn shutdown_write(&mut self) -> io::Result<()>
+    where
+        W: ShutdownWrite,
+    {
+        self.writer.shutdown_write()
+    }
+
+    /// Send `item` whenever `interval` passes without a frame being written.
+    /// See [`FramedWrite::set_heartbeat`].
+    pub fn set_heartbeat<I>(&mut self, item: I, interval: Duration) -> Result<(), E::Error>
+    where
+        E: Encoder<I>,
+    {
+        self.writer.set_heartbeat(item, interval)
+    }
+
+    /// Send the heartbeat frame if it is due.
+    /// See [`FramedWrite::maybe_send_heartbeat`].
+    pub fn maybe_send_heartbeat(&mut self) -> io::Result<bool> {
+        self.writer.maybe_send_heartbeat()
+    }
+
+    /// Write a frame, returning its encoded length.
+    /// See [`FramedWrite::framed_write_len`].
+    pub fn framed_write_len<I>(&mut self, item: I) -> Result<usize, E::Error>
+    where
+        E: Encoder<I>,
+    {
+        self.writer.framed_write_len(item)
+    }
+}
+
+// The most pending bytes shown by the `Debug` impls
+const DEBUG_PENDING_LEN: usize = 32;
+
+/// Formats the start of a buffer, marking it if it has been cut short.
+struct Pending<'a>(&'a [u8]);
+
+impl fmt::Debug for Pending<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self.0.len().min(DEBUG_PENDING_LEN);
+        write!(f, "{:?}", Bytes::copy_from_slice(&self.0[..len]))?;
+        if len < self.0.len() {
+            write!(f, "...")?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: fmt::Debug, D: fmt::Debug> fmt::Debug for FramedRead<R, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FramedRead")
+            .field("inner", &self.inner)
+            .field("decoder", &self.decoder)
+            .field("buffer_len", &self.buf.len())
+            .field("buffer_capacity", &self.buf.capacity())
+            .field("pending", &Pending(&self.buf))
+            .finish()
+    }
+}
+
+impl<W: fmt::Debug, E: fmt::Debug> fmt::Debug for FramedWrite<W, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FramedWrite")
+            .field("inner", &self.inner)
+            .field("encoder", &self.encoder)
+            .field("buffer_len", &self.buf.len())
+            .field("pending", &Pending(&self.buf))
+            .finish()
+    }
+}
+
+impl<R, W, D, E> fmt::Debug for Framed<R, W, D, E>
+where
+    R: fmt::Debug,
+    W: fmt::Debug,
+    D: fmt::Debug,
+    E: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Framed")
+            .field("reader", &self.reader)
+            .field("writer", &self.writer)
+            .finish()
+    }
+}
+
+impl<R: Read, W, D: Decoder, E> Framed<R, W, D, E> {
+    /// Decode a frame from the bytes already in the read buffer.
+    /// See [`FramedRead::peek_frame`].
+    pub fn peek_frame(&mut self) -> Result<Option<D::Item>, D::Error> {
+        self.reader.peek_frame()
+    }
+
+    /// Read at most once and try to decode a frame.
+    /// See [`FramedRead::framed_read_once`].
+    pub fn framed_read_once(&mut self) -> Result<Option<D::Item>, D::Error> {
+        self.reader.framed_read_once()
+    }
+
+    /// Read once and decode every complete frame.
+    /// See [`FramedRead::framed_read_batch`].
+    pub fn framed_read_batch(
+        &mut self,
+        max_frames: Option<usize>,
+    ) -> Result<Vec<D::Item>, D::Error> {
+        self.reader.framed_read_batch(max_frames)
+    }
+
+    /// Read a frame or `None` at a clean end of stream.
+    /// See [`FramedRead::framed_read_opt`].
+    pub fn framed_read_opt(&mut self) -> Result<Option<D::Item>, D::Error> {
+        self.reader.framed_read_opt()
+    }
+
+    /// Read every frame up to EOF.
+    /// See [`FramedRead::framed_read_drain_eof`].
+    pub fn framed_read_drain_eof(&mut self) -> Result<Vec<D::Item>, D::Error> {
+        self.reader.framed_read_drain_eof()
+    }
+
+    /// Read a frame along with its length on the wire.
+    /// See [`FramedRead::framed_read_with_len`].
+    pub fn framed_read_with_len(&mut self) -> Result<(D::Item, usize), D::Error> {
+        self.reader.framed_read_with_len()
+    }
+
+    /// Read a frame and the time spent waiting for it.
+    /// See [`FramedRead::framed_read_timed`].
+    pub fn framed_read_timed(&mut self) -> Result<(D::Item, Duration), D::Error> {
+        self.reader.framed_read_timed()
+    }
+
+    /// Read once into the read buffer without decoding.
+    /// See [`FramedRead::fill_buf`].
+    pub fn fill_buf(&mut self) -> io::Result<usize> {
+        self.reader.fill_buf()
+    }
+
+    /// Read a frame without blocking. See [`FramedRead::try_framed_read`].
+    pub fn try_framed_read(&mut self) -> Result<Option<D::Item>, D::Error> {
+        self.reader.try_framed_read()
+    }
+}
+
+/// What happened during one call to [`Framed::step`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FramedEvent<I> {
+    /// A frame was decoded.
+    ReadReady(I),
+    /// No complete frame is available yet; step again once the stream is
+    /// readable.
+    WouldBlock,
+    /// The peer closed the stream with no bytes left over.
+    Closed,
+}
+
+impl<R: Read, W: Write, D: Decoder, E> Framed<R, W, D, E> {
+    /// Do one unit of non-blocking work, for driving a `Framed` from a
+    /// hand-written event loop over non-blocking streams.
+    ///
+    /// Frames waiting in the write buffer are sent as far as the stream
+    /// allows, as [`Framed::try_flush`] does. Then a frame already complete
+    /// in the read buffer is returned if there is one; otherwise there is
+    /// a single `read`, after which any frame it completed is returned.
+    /// A `read` that returns an error of kind `ErrorKind::WouldBlock`, or
+    /// that leaves the frame incomplete, gives [`FramedEvent::WouldBlock`].
+    ///
+    /// EOF with bytes of a partial frame left in the buffer is an error, as
+    /// for `framed_read`.
+    pub fn step(&mut self) -> Result<FramedEvent<D::Item>, D::Error> {
+        self.writer.try_flush()?;
+
+        let reader = &mut self.reader;
+        reader.check_poisoned()?;
+        // There is no point asking the decoder again about a buffer it has
+        // already said is too short
+        if reader.stalled_len != Some(reader.buf.len()) {
+            if let Some(item) = reader.decode()? {
+                return Ok(FramedEvent::ReadReady(item));
+            }
+        }
+
+        let bytes_read = match reader.read_to_buf() {
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(FramedEvent::WouldBlock),
+            Err(e) => return Err(e.into()),
+        };
+        match reader.decode_frame(bytes_read) {
+            Ok(Some(item)) => Ok(FramedEvent::ReadReady(item)),
+            Ok(None) => Ok(FramedEvent::WouldBlock),
+            Err(_) if reader.eof => Ok(FramedEvent::Closed),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl<R: Read, D> FramedRead<R, D> {
+    /// Perform a single `read` on the underlying `Read` object, adding the
+    /// bytes to the read buffer without decoding them.
+    ///
+    /// Returns the number of bytes read, which is 0 at EOF. Follow this with
+    /// repeated calls to [`FramedRead::peek_frame`] to decode every frame
+    /// that arrived in the one read.
+    pub fn fill_buf(&mut self) -> io::Result<usize> {
+        self.read_to_buf()
+    }
+
+    /// As `read_to_buf`, except that while the bytes handed over at creation
+    /// may still hold a whole frame they are reported as just read, so that
+    /// they are decoded before waiting on the inner object.
+    fn read_more(&mut self) -> io::Result<usize> {
+        if self.seeded && !self.buf.is_empty() {
+            self.check_poisoned()?;
+            return Ok(self.buf.len());
+        }
+        self.read_to_buf()
+    }
+
+    /// Perform a single `read` on the inner object, placing the bytes
+    /// directly into the spare capacity at the end of the buffer.
+    fn read_to_buf(&mut self) -> io::Result<usize> {
+        self.check_poisoned()?;
+        self.seeded = false;
+        let mut chunk = self.read_chunk_size;
+        if let Some(max) = self.max_buffer_size {
+            chunk = chunk.min(max.saturating_sub(self.buf.len()));
+        }
+        let chunk = chunk.max(1);
+        self.buf.reserve(chunk);
+
+        let dst = self.buf.chunk_mut();
+        let len = chunk.min(dst.len());
+        // SAFETY: the first `len` bytes of the spare capacity are zeroed
+        // before being handed to `read` as an initialised slice.
+        let dst = unsafe {
+            ptr::write_bytes(dst.as_mut_ptr(), 0, len);
+            slice::from_raw_parts_mut(dst.as_mut_ptr(), len)
+        };
+
+        // A signal arriving mid-read is not an error, so just try again,
+        // as for a read that returned nothing while `max_empty_reads` allows.
+        // Anything else, including a dead connection, is passed on.
+        let mut empty_reads = 0;
+        let bytes_read = loop {
+            match self.inner.read(dst) {
+                Ok(0) if self.max_empty_reads > 0 => {
+                    if empty_reads == self.max_empty_reads {
+                        return Err(Error::new(ErrorKind::UnexpectedEof, "too many empty reads"));
+                    }
+                    empty_reads += 1;
+                }
+                Ok(n) => break n.min(len),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        };
+        // SAFETY: all `len` bytes were initialised above and we never
+        // advance past the number of bytes that `read` filled.
+        unsafe { self.buf.advance_mut(bytes_read) };
+        self.total_read += bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+impl<R: Seek, D> FramedRead<R, D> {
+    /// Seek the underlying object, discarding the bytes in the read buffer
+    /// so that the next frame is decoded from the new position.
+    ///
+    /// `SeekFrom::Current` is taken relative to the bytes consumed by the
+    /// decoder rather than those read ahead into the buffer, so seeking by
+    /// 0 moves back to the start of the next undecoded frame. The decoder
+    /// is left as it is, so `pos` should be the start of a frame.
+    pub fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Current(n) => {
+                let buffered = i64::try_from(self.buf.len()).map_err(Error::other)?;
+                SeekFrom::Current(n - buffered)
+            }
+            pos => pos,
+        };
+        let offset = self.inner.seek(pos)?;
+        self.buf.clear();
+        self.eof = false;
+        self.stalled_len = None;
+        self.seeded = false;
+        Ok(offset)
+    }
+}
+
+impl<R, D: Decoder> FramedRead<R, D> {
+    // Every call to the decoder goes through one of these so that an error
+    // poisons the `FramedRead` and a decoded frame may release memory.
+
+    fn decode(&mut self) -> Result<Option<D::Item>, D::Error> {
+        let before = self.buf.len();
+        let result = self.decoder.decode(&mut self.buf);
+        self.after_decode(result, before, true)
+    }
+
+    fn decode_up_to(&mut self, max: usize) -> Result<Vec<D::Item>, D::Error> {
+        let mut items = Vec::new();
+        while items.len() < max {
+            match self.decode()? {
+                Some(item) => items.push(item),
+                None => break,
+            }
+        }
+        Ok(items)
+    }
+
+    fn decode_eof(&mut self) -> Result<Option<D::Item>, D::Error> {
+        let before = self.buf.len();
+        let result = self.decoder.decode_eof(&mut self.buf);
+        // Running out of bytes at EOF is reported by the caller
+        self.after_decode(result, before, false)
+    }
+
+    fn decode_into(&mut self, out: &mut D::Item) -> Result<bool, D::Error> {
+        let before = self.buf.len();
+        match self.decoder.decode_into(&mut self.buf, out) {
+            Ok(decoded) => {
+                self.check_progress(before, decoded, true)?;
+                if decoded {
+                    self.maybe_shrink();
+                }
+                Ok(decoded)
+            }
+            Err(e) => {
+                self.poisoned = true;
+                Err(e)
+            }
+        }
+    }
+
+    fn decode_ready(&mut self) -> Result<Vec<D::Item>, D::Error> {
+        let before = self.buf.len();
+        // Every whole frame is decoded, so only a partial one can be left
+        self.seeded = false;
+        match self.decoder.decode_ready(&mut self.buf) {
+            Ok(items) => {
+                self.check_progress(before, !items.is_empty(), true)?;
+                if !items.is_empty() {
+                    self.maybe_shrink();
+                }
+                Ok(items)
+            }
+            Err(e) => {
+                self.poisoned = true;
+                Err(e)
+            }
+        }
+    }
+
+    fn after_decode(
+        &mut self,
+        result: Result<Option<D::Item>, D::Error>,
+        before: usize,
+        check_stall: bool,
+    ) -> Result<Option<D::Item>, D::Error> {
+        match result {
+            Ok(Some(item)) => {
+                self.check_progress(before, true, check_stall)?;
+                self.maybe_shrink();
+                Ok(Some(item))
+            }
+            Ok(None) => {
+                self.check_progress(before, false, check_stall)?;
+                Ok(None)
+            }
+            Err(e) => {
+                self.poisoned = true;
+                Err(e)
+            }
+        }
+    }
+
+    /// With [`FramedRead::set_check_progress`] enabled, fail if the decoder
+    /// returned a frame without consuming any bytes, or, when `check_stall`
+    /// is set, needed more bytes twice in a row without the buffer changing.
+    fn check_progress(
+        &mut self,
+        before: usize,
+        decoded: bool,
+        check_stall: bool,
+    ) -> io::Result<()> {
+        let len = self.buf.len();
+        let stalled = len == before && !decoded;
+        if !decoded {
+            self.seeded = false;
+        }
+        if self.check_progress
+            && ((len == before && decoded)
+                || (check_stall && stalled && self.stalled_len == Some(len)))
+        {
+            self.poisoned = true;
+            return Err(Error::other("decoder made no progress"));
+        }
+        // Also lets `Framed::step` skip decoding a buffer known to be short
+        self.stalled_len = stalled.then_some(len);
+        Ok(())
+    }
+}
+
+impl<R, D: Decoder> FramedRead<R, D> {
+    /// Returns how much of the frame being received has arrived, as
+    /// `(bytes_so_far, expected_total)` in bytes on the wire.
+    ///
+    /// This is `None` unless the decoder implements
+    /// [`Decoder::expected_len`] and enough of the frame has been read for
+    /// it to know the total, for example once a length header is complete.
+    /// It suits progress reporting for large frames over slow links.
+    pub fn frame_progress(&self) -> Option<(usize, usize)> {
+        let total = self.decoder.expected_len(&self.buf)?;
+        Some((self.buf.len().min(total), total))
+    }
+}
+
+impl<R: Read, D: Decoder> FramedRead<R, D> {
+    /// Attempt to decode a frame after a read of `bytes_read` bytes.
+    /// `Ok(None)` means that more data is needed.
+    fn decode_frame(&mut self, bytes_read: usize) -> Result<Option<D::Item>, D::Error> {
+        self.eof = false;
+        if bytes_read == 0 {
+            // The peer has gone but a complete frame may still be
+            // sitting in the buffer from an earlier read.
+            return match self.decode_eof()? {
+                Some(item) => Ok(Some(item)),
+                None => {
+                    self.eof = self.buf.is_empty();
+                    Err(Error::new(ErrorKind::ConnectionReset, "Server connection reset").into())
+                }
+            };
+        }
+        let item = self.decode()?;
+        if item.is_none() {
+            self.check_buffer_size()?;
+        }
+        Ok(item)
+    }
+
+    /// As `decode_frame` but decoding into `out`. Returns `true` once `out`
+    /// holds a new frame.
+    fn decode_frame_into(
+        &mut self,
+        bytes_read: usize,
+        out: &mut D::Item,
+    ) -> Result<bool, D::Error> {
+        self.eof = false;
+        let decoded = self.decode_into(out)?;
+        if !decoded && bytes_read == 0 {
+            // At EOF `decode_frame` either yields a frame or an error
+            return Ok(match self.decode_frame(0)? {
+                Some(item) => {
+                    *out = item;
+                    true
+                }
+                None => false,
+            });
+        }
+        if !decoded {
+            self.check_buffer_size()?;
+        }
+        Ok(decoded)
+    }
+
+    /// Decode a frame from the bytes already in the read buffer, without
+    /// reading from the underlying `Read` object.
+    ///
+    /// Returns `Ok(None)` if the buffer does not yet hold a complete frame,
+    /// in which case the buffered bytes are kept for the next read. Calling
+    /// this repeatedly drains every complete frame picked up by an earlier
+    /// read.
+    pub fn peek_frame(&mut self) -> Result<Option<D::Item>, D::Error> {
+        self.check_poisoned()?;
+        self.decode()
+    }
+
+    /// Perform at most one `read` on the underlying `Read` object and then
+    /// try once to decode a frame.
+    ///
+    /// Returns `Ok(None)` if the frame is not yet complete, keeping the
+    /// bytes read so far for the next call. Unlike `framed_read` this never
+    /// blocks more than once, which lets a caller interleave reading with
+    /// other work.
+    pub fn framed_read_once(&mut self) -> Result<Option<D::Item>, D::Error> {
+        let bytes_read = self.read_more()?;
+        self.decode_frame(bytes_read)
+    }
+
+    /// Perform one `read` on the underlying `Read` object and decode every
+    /// complete frame in the buffer, using [`Decoder::decode_ready`].
+    ///
+    /// Frames already complete in the buffer, for example left there by
+    /// `framed_read`, are returned without reading. The returned `Vec` is
+    /// empty if no frame is complete yet. At EOF this behaves as
+    /// `framed_read`: frames still in the buffer are returned and once there
+    /// are none left an error is returned.
+    ///
+    /// With `max_frames` set, at most that many frames are decoded, calling
+    /// [`Decoder::decode`] directly, and the rest stay in the buffer so that
+    /// a flood of small frames cannot keep the caller busy. They are returned
+    /// by the next call, which only reads once they have all been taken.
+    /// A `max_frames` of `Some(0)` is rejected with an error of kind
+    /// `ErrorKind::InvalidInput`, as no frame could ever be returned.
+    pub fn framed_read_batch(
+        &mut self,
+        max_frames: Option<usize>,
+    ) -> Result<Vec<D::Item>, D::Error> {
+        if max_frames == Some(0) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "batch size must be greater than zero",
+            )
+            .into());
+        }
+        self.check_poisoned()?;
+        // There is no point asking the decoder again about a buffer it has
+        // already said is too short
+        if !self.buf.is_empty() && self.stalled_len != Some(self.buf.len()) {
+            let items = self.decode_batch(max_frames)?;
+            if !items.is_empty() {
+                return Ok(items);
+            }
+        }
+        let bytes_read = self.read_more()?;
+        if bytes_read == 0 {
+            // Nothing new arrived, so all that is left is a frame at EOF
+            return self
+                .decode_frame(bytes_read)
+                .map(|item| item.into_iter().collect());
+        }
+        self.eof = false;
+        let items = self.decode_batch(max_frames)?;
+        if items.is_empty() {
+            self.check_buffer_size()?;
+        }
+        Ok(items)
+    }
+
+    fn decode_batch(&mut self, max_frames: Option<usize>) -> Result<Vec<D::Item>, D::Error> {
+        match max_frames {
+            Some(max) => self.decode_up_to(max),
+            None => self.decode_ready(),
+        }
+    }
+
+    /// Read a frame, telling a clean end of stream apart from an error.
+    ///
+    /// Returns `Ok(None)` if the underlying `Read` object reached EOF with
+    /// no bytes left in the buffer. EOF part way through a frame, leaving
+    /// bytes the decoder cannot use, is still an error.
+    pub fn framed_read_opt(&mut self) -> Result<Option<D::Item>, D::Error> {
+        loop {
+            let bytes_read = self.read_more()?;
+            match self.decode_frame(bytes_read) {
+                Ok(Some(item)) => return Ok(Some(item)),
+                Ok(None) => {}
+                Err(_) if self.eof => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Read until the underlying `Read` object reaches EOF, returning every
+    /// frame that arrives on the way, including those still in the buffer
+    /// and any final frame produced by [`Decoder::decode_eof`].
+    ///
+    /// This is the clean shutdown companion to `framed_read_batch`: use it
+    /// once the peer is known to be finishing up. Frames are decoded as the
+    /// bytes arrive so the buffer does not have to hold everything at once.
+    /// If bytes of a partial frame are left over at EOF the frames read so
+    /// far are discarded and an error is returned, as for `framed_read`.
+    pub fn framed_read_drain_eof(&mut self) -> Result<Vec<D::Item>, D::Error> {
+        let mut items = Vec::new();
+        loop {
+            let bytes_read = self.read_more()?;
+            if bytes_read == 0 {
+                break;
+            }
+            while let Some(item) = self.decode()? {
+                items.push(item);
+            }
+            self.check_buffer_size()?;
+        }
+
+        while let Some(item) = self.decode_eof()? {
+            items.push(item);
+        }
+        self.eof = self.buf.is_empty();
+        if !self.eof {
+            return Err(Error::new(ErrorKind::ConnectionReset, "Server connection reset").into());
+        }
+        Ok(items)
+    }
+
+    /// Read a frame as `framed_read` does, also returning the number of
+    /// bytes the frame occupied on the wire.
+    ///
+    /// The length is the number of bytes the decoder consumed from the read
+    /// buffer, so it relies on the decoder removing exactly the bytes of
+    /// the frame it returns.
+    pub fn framed_read_with_len(&mut self) -> Result<(D::Item, usize), D::Error> {
+        loop {
+            let bytes_read = self.read_more()?;
+            let len = self.buf.len();
+            if let Some(item) = self.decode_frame(bytes_read)? {
+                return Ok((item, len - self.buf.len()));
+            }
+        }
+    }
+
+    /// Read a frame as `framed_read` does, also returning how long the call
+    /// took.
+    ///
+    /// The duration is wall-clock time measured across the whole call,
+    /// including every blocking read on the underlying `Read` object and the
+    /// time spent decoding, so a slow peer shows up as a long duration.
+    pub fn framed_read_timed(&mut self) -> Result<(D::Item, Duration), D::Error> {
+        let start = Instant::now();
+        let item = self.framed_read()?;
+        Ok((item, start.elapsed()))
+    }
+
+    /// Read a frame without blocking, for use with a non-blocking `Read`
+    /// object such as a `TcpStream` after `set_nonblocking(true)`.
+    ///
+    /// Reads until a frame is decoded or the underlying `read` returns an
+    /// error of kind `ErrorKind::WouldBlock`. In the latter case a frame
+    /// already complete in the buffer is returned if there is one, otherwise
+    /// `Ok(None)` means "no frame yet, try again when the stream is
+    /// readable". Bytes of a partial frame are kept for the next call.
+    pub fn try_framed_read(&mut self) -> Result<Option<D::Item>, D::Error> {
+        let mut decoded = false;
+        loop {
+            let bytes_read = match self.read_more() {
+                // The buffer has not changed since the last decode
+                Err(e) if e.kind() == ErrorKind::WouldBlock && decoded => return Ok(None),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return self.decode(),
+                Ok(n) => n,
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(item) = self.decode_frame(bytes_read)? {
+                return Ok(Some(item));
+            }
+            decoded = true;
+        }
+    }
+
+    /// Turn the `FramedRead` into an iterator over the frames it reads.
+    pub fn iter(self) -> FramedIter<R, D> {
+        FramedIter {
+            inner: self,
+            done: false,
+        }
+    }
+}
+
+/// An iterator over the frames read by a [`FramedRead`].
+///
+/// Each frame is yielded as `Ok(item)`. The iterator ends when the
+/// underlying `Read` object reaches EOF with no bytes left over. Any other
+/// error, including EOF part way through a frame, is yielded once as
+/// `Err` and then the iterator ends.
+#[derive(Debug)]
+pub struct FramedIter<R, D> {
+    inner: FramedRead<R, D>,
+    done: bool,
+}
+
+impl<R, D> FramedIter<R, D> {
+    /// Consumes the iterator, returning the `FramedRead`.
+    pub fn into_inner(self) -> FramedRead<R, D> {
+        self.inner
+    }
+}
+
+impl<R: Read, D: Decoder> Iterator for FramedIter<R, D> {
+    type Item = Result<D::Item, D::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.inner.framed_read_opt() {
+            Ok(Some(item)) => Some(Ok(item)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<R: Read, D: Decoder> IntoIterator for FramedRead<R, D> {
+    type Item = Result<D::Item, D::Error>;
+    type IntoIter = FramedIter<R, D>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<R: Read + ReadTimeout, D: Decoder> FramedRead<R, D> {
+    /// Read a frame, waiting no longer than `timeout` for each read on the
+    /// underlying stream.
+    ///
+    /// If the timeout expires an error of kind `ErrorKind::TimedOut` is
+    /// returned. Any part of a frame received before the timeout is kept in
+    /// the buffer so a later call carries on from where this one stopped.
+    /// The stream's previous read timeout is restored before returning.
+    pub fn framed_read_timeout(&mut self, timeout: Duration) -> Result<D::Item, D::Error> {
+        let previous = self.inner.read_timeout()?;
+        self.inner.set_read_timeout(Some(timeout))?;
+        let result = self.framed_read_until_timeout(None);
+        self.inner.set_read_timeout(previous)?;
+        result
+    }
+
+    /// Read a frame, giving up once `deadline` has passed.
+    ///
+    /// Unlike [`FramedRead::framed_read_timeout`] the limit covers the whole
+    /// frame however many reads it takes: the stream's read timeout is set
+    /// to the time remaining before each read. If the deadline passes before
+    /// a frame is complete an error of kind `ErrorKind::TimedOut` is
+    /// returned and the partial frame is kept in the buffer for a later
+    /// call. The stream's previous read timeout is restored before
+    /// returning.
+    pub fn framed_read_deadline(&mut self, deadline: Instant) -> Result<D::Item, D::Error> {
+        let previous = self.inner.read_timeout()?;
+        let result = self.framed_read_until_timeout(Some(deadline));
+        self.inner.set_read_timeout(previous)?;
+        result
+    }
+
+    fn framed_read_until_timeout(
+        &mut self,
+        deadline: Option<Instant>,
+    ) -> Result<D::Item, D::Error> {
+        let timed_out = || Error::new(ErrorKind::TimedOut, "Timed out reading frame");
+        loop {
+            if let Some(deadline) = deadline {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(timed_out().into());
+                }
+                self.inner.set_read_timeout(Some(remaining))?;
+            }
+            let bytes_read = match self.read_more() {
+                Ok(n) => n,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Err(timed_out().into())
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(item) = self.decode_frame(bytes_read)? {
+                return Ok(item);
+            }
+        }
+    }
+}
+
+impl<R: TryClone, D: Clone> FramedRead<R, D> {
+    /// Create a second `FramedRead` over a clone of the underlying stream,
+    /// using a clone of the decoder.
+    ///
+    /// The new reader starts with an empty buffer and the same settings as
+    /// this one. Each reader only sees the bytes it reads itself, so this
+    /// suits protocols where any reader may handle any frame, such as
+    /// several worker threads sharing one socket.
+    pub fn try_clone(&self) -> io::Result<Self> {
+        let mut clone =
+            FramedRead::with_capacity(self.inner.try_clone()?, self.decoder.clone(), self.capacity);
+        clone.read_chunk_size = self.read_chunk_size;
+        clone.shrink_threshold = self.shrink_threshold;
+        clone.max_buffer_size = self.max_buffer_size;
+        clone.max_empty_reads = self.max_empty_reads;
+        clone.check_progress = self.check_progress;
+        Ok(clone)
+    }
+}
+
+/// Streams that can be cloned into a second handle on the same resource.
+pub trait TryClone: Sized {
+    fn try_clone(&self) -> io::Result<Self>;
+}
+
+impl TryClone for TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        TcpStream::try_clone(self)
+    }
+}
+
+#[cfg(unix)]
+impl TryClone for UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        UnixStream::try_clone(self)
+    }
+}
+
+impl<W: Write + ShutdownWrite, E> FramedWrite<W, E> {
+    /// Write out all buffered frames and then shut down the writing half of
+    /// the underlying stream, so that the peer sees EOF.
+    ///
+    /// This suits protocols where the peer reads until the connection
+    /// ends. Reading from the other half of the stream still works.
+    pub fn shutdown_write(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.inner.shutdown_write()
+    }
+}
+
+/// Streams whose writing half can be closed on its own.
+pub trait ShutdownWrite {
+    fn shutdown_write(&self) -> io::Result<()>;
+}
+
+impl ShutdownWrite for TcpStream {
+    fn shutdown_write(&self) -> io::Result<()> {
+        self.shutdown(Shutdown::Write)
+    }
+}
+
+#[cfg(unix)]
+impl ShutdownWrite for UnixStream {
+    fn shutdown_write(&self) -> io::Result<()> {
+        self.shutdown(Shutdown::Write)
+    }
+}
+
+impl<T: ShutdownWrite + ?Sized> ShutdownWrite for &T {
+    fn shutdown_write(&self) -> io::Result<()> {
+        (**self).shutdown_write()
+    }
+}
+
+impl<T: ShutdownWrite + ?Sized> ShutdownWrite for &mut T {
+    fn shutdown_write(&self) -> io::Result<()> {
+        (**self).shutdown_write()
+    }
+}
+
+/// A source of the current time, so that time-based behaviour such as
+/// heartbeats and rate limits can be driven by a fake clock in tests.
+pub trait Clock {
+    fn now(&self) -> Instant;
+
+    /// Block for `duration`. The default implementation sleeps the current
+    /// thread.
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// The [`Clock`] that reads the system's monotonic clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Streams that can put a time limit on blocking reads.
+pub trait ReadTimeout {
+    fn read_timeout(&self) -> io::Result<Option<Duration>>;
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl ReadTimeout for TcpStream {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        TcpStream::read_timeout(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl<T: ReadTimeout + ?Sized> ReadTimeout for &T {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        (**self).read_timeout()
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_read_timeout(timeout)
+    }
+}
+
+impl<T: ReadTimeout + ?Sized> ReadTimeout for &mut T {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        (**self).read_timeout()
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_read_timeout(timeout)
+    }
+}
+
+#[cfg(unix)]
+impl ReadTimeout for UnixStream {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        UnixStream::read_timeout(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+// Expose the underlying descriptor so a framed stream can be registered with
+// `select`, `poll` and the like. `Framed` reports its reading half as that is
+// the one an event loop waits on.
+#[cfg(unix)]
+impl<R: AsRawFd, D> AsRawFd for FramedRead<R, D> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+#[cfg(unix)]
+impl<W: AsRawFd, E> AsRawFd for FramedWrite<W, E> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+#[cfg(unix)]
+impl<R: AsRawFd, W, D, E> AsRawFd for Framed<R, W, D, E> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.reader.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl<R: AsRawSocket, D> AsRawSocket for FramedRead<R, D> {
+    fn as_raw_socket(&self) -> RawSocket {
+        self.inner.as_raw_socket()
+    }
+}
+
+#[cfg(windows)]
+impl<W: AsRawSocket, E> AsRawSocket for FramedWrite<W, E> {
+    fn as_raw_socket(&self) -> RawSocket {
+        self.inner.as_raw_socket()
+    }
+}
+
+#[cfg(windows)]
+impl<R: AsRawSocket, W, D, E> AsRawSocket for Framed<R, W, D, E> {
+    fn as_raw_socket(&self) -> RawSocket {
+        self.reader.as_raw_socket()
+    }
+}
+
+/// Trait for reading frames
+pub trait FramedReader<I> {
+    /// The error returned when a frame cannot be read. For the framed types
+    /// in this crate this is the error type of the `Decoder`.
+    type Error;
+
+    fn framed_read(&mut self) -> Result<I, Self::Error>;
+
+    /// Read a frame into `out`, replacing its previous value.
+    ///
+    /// The framed types in this crate use [`Decoder::decode_into`] so that
+    /// a codec can reuse the allocations already held by `out`, which helps
+    /// in a hot loop reading large `Vec`-backed messages. The default
+    /// implementation just assigns the result of `framed_read`.
+    fn framed_read_into(&mut self, out: &mut I) -> Result<(), Self::Error> {
+        *out = self.framed_read()?;
+        Ok(())
+    }
+}
+
+/// Trait for writing frames
+pub trait FramedWriter<I> {
+    /// The error returned when a frame cannot be written. For the framed
+    /// types in this crate this is the error type of the `Encoder`.
+    type Error;
+
+    fn framed_write(&mut self, item: I) -> Result<(), Self::Error>;
+
+    /// Write a frame from a borrowed item.
+    ///
+    /// The framed types in this crate use [`Encoder::encode_ref`], so a
+    /// codec that overrides it sends the item without cloning it. The
+    /// default implementation clones `item` and calls `framed_write`.
+    fn framed_write_ref(&mut self, item: &I) -> Result<(), Self::Error>
+    where
+        I: Clone,
+    {
+        self.framed_write(item.clone())
+    }
+
+    /// Write a batch of frames.
+    ///
+    /// The framed types in this crate encode every item into the write
+    /// buffer and then write and flush them together. If an item fails to
+    /// encode the whole batch is abandoned: none of its frames are written
+    /// and the error is returned. Frames buffered before the call are
+    /// left in the buffer.
+    ///
+    /// The default implementation calls `framed_write` for each item in
+    /// turn, so items before a failing one have already been written.
+    fn framed_write_all<It>(&mut self, items: It) -> Result<(), Self::Error>
+    where
+        It: IntoIterator<Item = I>,
+        Self: Sized,
+    {
+        for item in items {
+            self.framed_write(item)?;
+        }
+        Ok(())
+    }
+}
+
+impl<I, R, D> FramedReader<I> for FramedRead<R, D>
+where
+    R: Read,
+    D: Decoder<Item = I>,
+{
+    type Error = D::Error;
+
+    fn framed_read(&mut self) -> Result<I, Self::Error> {
+        loop {
+            let bytes_read = self.read_more()?;
+            if let Some(item) = self.decode_frame(bytes_read)? {
+                return Ok(item);
+            }
+        }
+    }
+
+    fn framed_read_into(&mut self, out: &mut I) -> Result<(), Self::Error> {
+        loop {
+            let bytes_read = self.read_more()?;
+            if self.decode_frame_into(bytes_read, out)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<I, W, E> FramedWriter<I> for FramedWrite<W, E>
+where
+    W: Write,
+    E: Encoder<I>,
+{
+    type Error = E::Error;
+
+    fn framed_write(&mut self, item: I) -> Result<(), Self::Error> {
+        self.framed_write_len(item).map(|_| ())
+    }
+
+    fn framed_write_ref(&mut self, item: &I) -> Result<(), Self::Error>
+    where
+        I: Clone,
+    {
+        let hint = self.encoder.size_hint(item);
+        self.buffer_frame::<I, _>(hint, |encoder, buf| encoder.encode_ref(item, buf))?;
+        Ok(self.write_frames()?)
+    }
+
+    fn framed_write_all<It>(&mut self, items: It) -> Result<(), Self::Error>
+    where
+        It: IntoIterator<Item = I>,
+    {
+        let len = self.buf.len();
+        for item in items {
+            if let Err(e) = self.framed_write_buffered(item) {
+                self.buf.truncate(len);
+                return Err(e);
+            }
+        }
+        Ok(self.write_frames()?)
+    }
+}
+
+impl<R, W, D, E, I> FramedReader<I> for Framed<R, W, D, E>
+where
+    R: Read,
+    D: Decoder<Item = I>,
+{
+    type Error = D::Error;
+
+    fn framed_read(&mut self) -> Result<I, Self::Error> {
+        self.reader.framed_read()
+    }
+
+    fn framed_read_into(&mut self, out: &mut I) -> Result<(), Self::Error> {
+        self.reader.framed_read_into(out)
+    }
+}
+
+impl<R, W, D, E, I> FramedWriter<I> for Framed<R, W, D, E>
+where
+    W: Write,
+    E: Encoder<I>,
+{
+    type Error = E::Error;
+
+    fn framed_write(&mut self, item: I) -> Result<(), Self::Error> {
+        self.writer.framed_write(item)
+    }
+
+    fn framed_write_ref(&mut self, item: &I) -> Result<(), Self::Error>
+    where
+        I: Clone,
+    {
+        self.writer.framed_write_ref(item)
+    }
+
+    fn framed_write_all<It>(&mut self, items: It) -> Result<(), Self::Error>
+    where
+        It: IntoIterator<Item = I>,
+    {
+        self.writer.framed_write_all(items)
+    }
+}
+
+#[cfg(test)]
+// Some of the oldest tests are written in a style these lints reject
+#[allow(clippy::len_zero, clippy::useless_conversion, clippy::useless_vec)]
+mod tests {
+    use bytes::Buf;
+    use socket_server_mocker::{Instruction::*, ServerMocker};
+
+    use std::collections::VecDeque;
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum TestMsg {
+        U8(u8),
+        U16(u16),
+        Unrecognised,
+    }
+
+    #[derive(Debug, Clone)]
+    struct TestCodec;
+
+    impl Decoder for TestCodec {
+        type Item = TestMsg;
+        type Error = io::Error;
+
+        fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+            if src.len() < 1 {
+                return Ok(None);
+            }
+
+            let msg_len = src[0];
+            if src.len() < msg_len as usize + 1 {
+                return Ok(None);
+            }
+
+            src.advance(1);
+            let mut msg = src.split_to(msg_len as usize);
+
+            match msg_len {
+                1 => {
+                    let val = msg.get_u8();
+                    Ok(Some(TestMsg::U8(val)))
+                }
+                2 => {
+                    let val = msg.get_u16();
+                    Ok(Some(TestMsg::U16(val)))
+                }
+                _ => Ok(Some(TestMsg::Unrecognised)),
+            }
+        }
+    }
+
+    impl Encoder<TestMsg> for TestCodec {
+        type Error = io::Error;
+
+        fn encode(&mut self, item: TestMsg, dst: &mut BytesMut) -> Result<(), Self::Error> {
+            match item {
+                TestMsg::U8(val) => {
+                    dst.put_u8(1);
+                    dst.put_u8(val);
+                }
+                TestMsg::U16(val) => {
+                    dst.put_u8(2);
+                    dst.put_u16(val);
+                }
+                TestMsg::Unrecognised => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, ""))
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reader_valid_u8() {
+        let r = vec![1u8, 128];
+        let mut framed = FramedRead::new(&r[..], TestCodec);
+        let data = framed.framed_read().unwrap();
+        assert_eq!(data, TestMsg::U8(128));
+    }
+
+    #[test]
+    fn reader_valid_u16() {
+        let r = vec![2u8, 1, 128];
+        let mut framed = FramedRead::new(&r[..], TestCodec);
+        let data = framed.framed_read().unwrap();
+        assert_eq!(data, TestMsg::U16(2u16.pow(8) + 128));
+    }
+
+    #[test]
+    fn read_unrecognised() {
+        let r = vec![3u8, 1, 128, 0];
+        let mut framed = FramedRead::new(&r[..], TestCodec);
+        let data = framed.framed_read().unwrap();
+        assert_eq!(data, TestMsg::Unrecognised);
+    }
+
+    #[test]
+    fn read_zero_length() {
+        let r: Vec<u8> = Vec::new();
+        let mut framed = FramedRead::new(&r[..], TestCodec);
+        let data = framed.framed_read();
+        assert!(data.is_err());
+    }
+
+    #[test]
+    fn read_eof_mid_frame() {
+        let r = [2u8, 1];
+        let mut framed = FramedRead::new(&r[..], TestCodec);
+        let data = framed.framed_read();
+        assert_eq!(data.unwrap_err().kind(), ErrorKind::ConnectionReset);
+    }
+
+    #[test]
+    fn read_buffered_frame_after_eof() {
+        let r = [1u8, 12, 2, 4, 210];
+        let mut framed = FramedRead::new(&r[..], TestCodec);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(12));
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U16(1234));
+        assert!(framed.framed_read().is_err());
+    }
+
+    struct UntilEofCodec;
+
+    impl Decoder for UntilEofCodec {
+        type Item = Vec<u8>;
+        type Error = io::Error;
+
+        fn decode(&mut self, _src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+            Ok(None)
+        }
+
+        fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+            if src.is_empty() {
+                return Ok(None);
+            }
+            Ok(Some(src.split().to_vec()))
+        }
+    }
+
+    #[test]
+    fn read_decode_eof() {
+        let r = [1u8, 2, 3, 4];
+        let mut framed = FramedRead::new(&r[..], UntilEofCodec);
+        assert_eq!(framed.framed_read().unwrap(), vec![1, 2, 3, 4]);
+        assert!(framed.framed_read().is_err());
+    }
+
+    #[test]
+    fn drain_frames_at_eof() {
+        let r = [1u8, 12, 2, 4, 210];
+        let mut framed = FramedRead::new(&r[..], TestCodec);
+        assert_eq!(
+            framed.framed_read_drain_eof().unwrap(),
+            [TestMsg::U8(12), TestMsg::U16(1234)]
+        );
+        assert!(framed.framed_read_drain_eof().unwrap().is_empty());
+
+        // The last line has no newline so only `decode_eof` finds it
+        let r = b"one\ntwo\nthree";
+        let mut framed = FramedRead::with_capacity(&r[..], crate::codec::LinesCodec::new(), 4);
+        assert_eq!(
+            framed.framed_read_drain_eof().unwrap(),
+            ["one", "two", "three"]
+        );
+
+        let r = [1u8, 12, 2, 4];
+        let mut framed = FramedRead::new(&r[..], TestCodec);
+        let err = framed.framed_read_drain_eof().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ConnectionReset);
+    }
+
+    #[test]
+    fn read_with_capacity() {
+        let r = [2u8, 4, 210];
+        let mut framed = FramedRead::with_capacity(&r[..], TestCodec, 2);
+        assert_eq!(framed.buf.capacity(), 2);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U16(1234));
+    }
+
+    #[test]
+    fn write_with_capacity() {
+        let mut buf = vec![];
+        let mut framed = FramedWrite::with_capacity(&mut buf, TestCodec, 1);
+        framed.framed_write(TestMsg::U16(1234)).unwrap();
+        drop(framed);
+        assert_eq!(buf, vec![2, 4, 210]);
+    }
+
+    struct U32LenCodec;
+
+    impl Decoder for U32LenCodec {
+        type Item = Vec<u8>;
+        type Error = io::Error;
+
+        fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+            if src.len() < 4 {
+                return Ok(None);
+            }
+            let len = u32::from_be_bytes([src[0], src[1], src[2], src[3]]) as usize;
+            if src.len() < len + 4 {
+                return Ok(None);
+            }
+            src.advance(4);
+            Ok(Some(src.split_to(len).to_vec()))
+        }
+    }
+
+    /// A reader that hands out its data in awkwardly sized pieces
+    struct ChunkedReader {
+        data: Vec<u8>,
+        pos: usize,
+        chunk: usize,
+    }
+
+    impl Read for ChunkedReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.chunk).min(self.data.len() - self.pos);
+            buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn read_several_megabytes() {
+        let frames: Vec<Vec<u8>> = (0..64u32)
+            .map(|i| (0..65536u32).map(|j| (i + j) as u8).collect())
+            .collect();
+        let mut data = vec![];
+        for frame in &frames {
+            data.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+            data.extend_from_slice(frame);
+        }
+
+        let reader = ChunkedReader {
+            data,
+            pos: 0,
+            chunk: 7919,
+        };
+        let mut framed = FramedRead::new(reader, U32LenCodec);
+        for frame in &frames {
+            assert_eq!(&framed.framed_read().unwrap(), frame);
+        }
+        assert!(framed.framed_read().is_err());
+    }
+
+    #[test]
+    fn write_valid_u8() {
+        let mut buf = vec![];
+        let mut framed = FramedWrite::new(&mut buf, TestCodec);
+        framed.framed_write(TestMsg::U8(12)).ok();
+        drop(framed);
+        assert_eq!(buf, vec![1, 12]);
+    }
+
+    #[test]
+    fn write_valid_u16() {
+        let mut buf = vec![];
+        let mut framed = FramedWrite::new(&mut buf, TestCodec);
+        framed.framed_write(TestMsg::U16(1234)).ok();
+        drop(framed);
+        assert_eq!(buf, vec![2, 4, 210]);
+    }
+
+    /// A writer that accepts at most 3 bytes per call
+    struct ShortWriter(Vec<u8>);
+
+    impl Write for ShortWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = buf.len().min(3);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct BlobCodec;
+
+    impl Encoder<Vec<u8>> for BlobCodec {
+        type Error = io::Error;
+
+        fn encode(&mut self, item: Vec<u8>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+            dst.put_u8(item.len() as u8);
+            dst.extend_from_slice(&item);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_short_writes() {
+        let mut framed = FramedWrite::new(ShortWriter(vec![]), BlobCodec);
+        framed.framed_write(vec![1, 2, 3, 4, 5, 6, 7]).unwrap();
+        assert_eq!(framed.inner.0, vec![7, 1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[derive(Default)]
+    struct CountingWriter {
+        data: Vec<u8>,
+        writes: usize,
+        flushes: usize,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes += 1;
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    // Records the address and contents of each slice passed to
+    // `write_vectored`, accepting at most `limit` bytes per call
+    struct VectoredWriter {
+        slices: Vec<(*const u8, Vec<u8>)>,
+        limit: usize,
+    }
+
+    impl Write for VectoredWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.write_vectored(&[IoSlice::new(buf)])
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+            let mut written = 0;
+            for buf in bufs {
+                let n = buf.len().min(self.limit - written);
+                if n == 0 {
+                    break;
+                }
+                self.slices.push((buf.as_ptr(), buf[..n].to_vec()));
+                written += n;
+            }
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn vectored_write_without_copying() {
+        use crate::codec::LengthDelimitedCodec;
+
+        let writer = VectoredWriter {
+            slices: vec![],
+            limit: usize::MAX,
+        };
+        let mut framed = FramedWrite::new(writer, LengthDelimitedCodec::new());
+        let payload = BytesMut::from(&b"a large payload"[..]);
+        let address = payload.as_ptr();
+        framed.framed_write_vectored(payload).unwrap();
+        let slices = &framed.get_ref().slices;
+        assert_eq!(slices.len(), 2);
+        assert_eq!(slices[0].1, [0, 0, 0, 15]);
+        assert_eq!(slices[1], (address, b"a large payload".to_vec()));
+
+        // Partial writes carry on from where they stopped
+        let writer = VectoredWriter {
+            slices: vec![],
+            limit: 3,
+        };
+        let mut framed = FramedWrite::new(writer, LengthDelimitedCodec::new());
+        framed
+            .framed_write_buffered(BytesMut::from(&b"x"[..]))
+            .unwrap();
+        framed
+            .framed_write_vectored(BytesMut::from(&b"hello"[..]))
+            .unwrap();
+        let wire: Vec<u8> = framed
+            .get_ref()
+            .slices
+            .iter()
+            .flat_map(|s| s.1.clone())
+            .collect();
+        assert_eq!(wire, b"\0\0\0\x01x\0\0\0\x05hello");
+    }
+
+    #[test]
+    fn vectored_write_falls_back_to_encode() {
+        let mut framed = FramedWrite::new(CountingWriter::default(), TestCodec);
+        framed.framed_write_vectored(TestMsg::U16(0x0102)).unwrap();
+        assert_eq!(framed.get_ref().data, [2, 1, 2]);
+        assert!(framed.framed_write_vectored(TestMsg::Unrecognised).is_err());
+        assert!(!framed.is_poisoned());
+    }
+
+    // Fails once after accepting `fail_after` bytes, then works normally
+    struct FlakyWriter {
+        data: Vec<u8>,
+        fail_after: Option<usize>,
+    }
+
+    impl Write for FlakyWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = match self.fail_after {
+                Some(0) => {
+                    self.fail_after = None;
+                    return Err(ErrorKind::TimedOut.into());
+                }
+                Some(budget) => {
+                    let n = buf.len().min(budget);
+                    self.fail_after = Some(budget - n);
+                    n
+                }
+                None => buf.len(),
+            };
+            self.data.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn resume_after_partial_write() {
+        let writer = FlakyWriter {
+            data: vec![],
+            fail_after: Some(4),
+        };
+        let mut framed = FramedWrite::new(writer, TestCodec);
+        framed.framed_write(TestMsg::U8(1)).unwrap();
+        let err = framed.framed_write(TestMsg::U16(0x0203)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert!(framed.is_poisoned());
+        assert_eq!(framed.get_ref().data, [1, 1, 2, 2]);
+        assert_eq!(framed.bytes_pending(), 1);
+
+        framed.resume_write().unwrap();
+        assert!(!framed.is_poisoned());
+        framed.framed_write(TestMsg::U8(4)).unwrap();
+        assert_eq!(framed.get_ref().data, [1, 1, 2, 2, 3, 1, 4]);
+    }
+
+    // Accepts at most `chunk` bytes per write, and only on every other call
+    struct TrickleWriter {
+        data: Vec<u8>,
+        chunk: usize,
+        ready: bool,
+    }
+
+    impl Write for TrickleWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.ready = !self.ready;
+            if !self.ready {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+            let n = buf.len().min(self.chunk);
+            self.data.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Clone)]
+    struct FakeClock(std::sync::Arc<std::sync::Mutex<Instant>>);
+
+    impl FakeClock {
+        fn new() -> Self {
+            Self(std::sync::Arc::new(std::sync::Mutex::new(Instant::now())))
+        }
+
+        fn advance(&self, by: Duration) {
+            *self.0.lock().unwrap() += by;
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.advance(duration);
+        }
+    }
+
+    #[test]
+    fn extend_stores_first_error() {
+        let mut framed = FramedWrite::new(vec![], TestCodec);
+        framed.extend([TestMsg::U8(1), TestMsg::U16(2)]);
+        assert!(framed.last_error().is_none());
+        assert_eq!(framed.get_ref(), &[1, 1, 2, 0, 2]);
+
+        framed.extend([TestMsg::U8(3), TestMsg::Unrecognised, TestMsg::U8(4)]);
+        assert_eq!(framed.get_ref(), &[1, 1, 2, 0, 2, 1, 3]);
+        let err = framed.take_last_error().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(framed.last_error().is_none());
+    }
+
+    #[test]
+    fn try_write_with_backpressure() {
+        let writer = TrickleWriter {
+            data: vec![],
+            chunk: 2,
+            ready: true,
+        };
+        let mut framed = FramedWrite::new(writer, TestCodec);
+        assert!(!framed.try_framed_write(TestMsg::U16(0x0102)).unwrap());
+        assert!(framed.get_ref().data.is_empty());
+        assert_eq!(framed.bytes_pending(), 3);
+
+        let mut attempts = 1;
+        while !framed.try_flush().unwrap() {
+            attempts += 1;
+        }
+        assert_eq!(framed.get_ref().data, [2, 1, 2]);
+        assert!(attempts > 1);
+
+        // Frames queued behind a partial one go out in order
+        assert!(!framed.try_framed_write(TestMsg::U16(0x0304)).unwrap());
+        assert!(!framed.try_framed_write(TestMsg::U8(5)).unwrap());
+        while !framed.try_flush().unwrap() {}
+        assert_eq!(framed.get_ref().data, [2, 1, 2, 2, 3, 4, 1, 5]);
+        assert!(framed.write_buffer().is_empty());
+        assert!(!framed.is_poisoned());
+    }
+
+    #[test]
+    fn drop_flushes_buffered_frames() {
+        let mut wire = vec![];
+        let mut framed = FramedWrite::new(&mut wire, TestCodec);
+        framed.framed_write_buffered(TestMsg::U8(7)).unwrap();
+        drop(framed);
+        assert_eq!(wire, [1, 7]);
+
+        // Except when asked to give up the writer as it is
+        let mut framed = FramedWrite::new(vec![], TestCodec);
+        framed.framed_write_buffered(TestMsg::U8(7)).unwrap();
+        let framed = framed.map_encoder(|codec| codec);
+        assert!(framed.into_inner().is_empty());
+
+        let mut framed = FramedWrite::new(vec![], TestCodec);
+        framed.framed_write_buffered(TestMsg::U8(7)).unwrap();
+        assert_eq!(framed.into_inner_flushed().unwrap(), [1, 7]);
+    }
+
+    #[test]
+    fn split_and_reunite() {
+        let mut wire = vec![];
+        let mut writer = FramedWrite::new(&mut wire, TestCodec);
+        for i in 0..2 {
+            writer.framed_write(TestMsg::U16(i)).unwrap();
+        }
+        drop(writer);
+
+        let framed = Framed::new(&wire[..], vec![], TestCodec, TestCodec);
+        let (mut reader, mut writer) = framed.split();
+        assert_eq!(reader.framed_read().unwrap(), TestMsg::U16(0));
+        writer.framed_write(TestMsg::U8(1)).unwrap();
+        writer.framed_write_buffered(TestMsg::U8(2)).unwrap();
+
+        let mut framed = Framed::reunite(reader, writer);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U16(1));
+        framed.framed_write(TestMsg::U8(3)).unwrap();
+        assert_eq!(framed.get_ref().1, &[1, 1, 1, 2, 1, 3]);
+    }
+
+    #[test]
+    fn keep_one_direction() {
+        let wire = [1u8, 7, 1, 8];
+        let framed = Framed::new(&wire[..], vec![], TestCodec, TestCodec);
+        let mut reader = framed.into_framed_read();
+        assert_eq!(reader.framed_read().unwrap(), TestMsg::U8(7));
+        assert_eq!(reader.framed_read().unwrap(), TestMsg::U8(8));
+
+        let framed = Framed::new(&wire[..], vec![], TestCodec, TestCodec);
+        let mut writer = framed.into_framed_write();
+        writer.framed_write(TestMsg::U8(9)).unwrap();
+        assert_eq!(writer.into_inner(), [1, 9]);
+    }
+
+    #[test]
+    fn total_bytes_counted() {
+        let wire = [1u8, 7, 2, 4, 210, 1];
+        // A small capacity takes several reads per frame
+        let mut framed = FramedRead::with_capacity(&wire[..], TestCodec, 2);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(7));
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U16(1234));
+        assert_eq!(framed.total_bytes_read(), 6);
+        assert_eq!(framed.bytes_buffered(), 1);
+
+        let mut framed = Framed::new(&wire[..], vec![], TestCodec, TestCodec);
+        framed.framed_write(TestMsg::U16(1)).unwrap();
+        framed.framed_write_vectored(TestMsg::U8(1)).unwrap();
+        framed.framed_write_buffered(TestMsg::U8(2)).unwrap();
+        assert_eq!(framed.total_bytes_written(), 5);
+        assert_eq!(framed.total_bytes_read(), 0);
+    }
+
+    #[test]
+    fn capacity_from_decoder_hint() {
+        use crate::codec::FixedLengthCodec;
+
+        // Room for a thousand records
+        struct Records(FixedLengthCodec);
+
+        impl Decoder for Records {
+            type Item = BytesMut;
+            type Error = io::Error;
+
+            fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+                self.0.decode(src)
+            }
+
+            fn capacity_hint(&self) -> Option<usize> {
+                Some(self.0.record_len() * 1000)
+            }
+        }
+
+        let r = [0u8; 128];
+        let mut framed = FramedRead::new(&r[..], Records(FixedLengthCodec::new(64)));
+        assert_eq!(framed.read_buffer().capacity(), 64_000);
+        assert_eq!(framed.framed_read().unwrap().len(), 64);
+
+        let framed = FramedRead::new(&r[..], TestCodec);
+        assert_eq!(framed.read_buffer().capacity(), INITIAL_CAPACITY);
+    }
+
+    #[test]
+    fn seed_read_buffer() {
+        // Nothing may be read while the frames are already here
+        let reader = ScriptedReader::new(vec![Err(ErrorKind::Unsupported.into())]);
+        let initial = BytesMut::from(&[1, 7, 1, 8, 2, 4][..]);
+        let mut framed = FramedRead::with_initial_buffer(reader, TestCodec, initial);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(7));
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(8));
+        assert_eq!(framed.get_ref().0.len(), 1);
+        // Only a partial frame is left, so the next frame needs a read
+        let err = framed.framed_read().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+
+        let reader = ScriptedReader::new(vec![Ok(vec![210])]);
+        let initial = BytesMut::from(&[2, 4][..]);
+        let mut framed = Framed::with_initial_buffer(reader, vec![], TestCodec, TestCodec, initial);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U16(1234));
+    }
+
+    #[test]
+    fn frame_callbacks() {
+        let encoded = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+        let log = encoded.clone();
+        let mut framed = FramedWrite::new(vec![], TestCodec).on_frame_encoded(
+            move |item: &TestMsg, wire: &[u8]| log.borrow_mut().push((item.clone(), wire.to_vec())),
+        );
+        framed.framed_write(TestMsg::U16(0x0102)).unwrap();
+        assert_eq!(*encoded.borrow(), [(TestMsg::U16(0x0102), vec![2, 1, 2])]);
+        let wire = framed.into_inner();
+
+        let mut decoded = vec![];
+        let mut framed = FramedRead::new(&wire[..], TestCodec)
+            .on_frame_decoded(|item: &TestMsg| decoded.push(item.clone()));
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U16(0x0102));
+        drop(framed);
+        assert_eq!(decoded, [TestMsg::U16(0x0102)]);
+    }
+
+    // Counts the bytes read from it
+    struct CountingReader<'a> {
+        data: &'a [u8],
+        bytes: usize,
+    }
+
+    impl Read for CountingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.data.read(buf)?;
+            self.bytes += n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn read_from_unwrapped_buf_reader() {
+        use std::io::BufRead;
+
+        let mut wire = b"HELLO\n".to_vec();
+        for i in 0..100 {
+            wire.extend_from_slice(&[2, 0, i]);
+        }
+        let reader = CountingReader {
+            data: &wire,
+            bytes: 0,
+        };
+        let mut reader = BufReader::with_capacity(64, reader);
+        let mut greeting = String::new();
+        reader.read_line(&mut greeting).unwrap();
+        assert_eq!(greeting, "HELLO\n");
+
+        let mut framed = FramedRead::from_buf_read(reader, TestCodec);
+        assert_eq!(framed.read_buffer().len(), 58);
+        for i in 0..100u8 {
+            assert_eq!(framed.framed_read().unwrap(), TestMsg::U16(i.into()));
+        }
+        // Every byte was read from the stream once and into the buffer
+        // that gets decoded, with no second buffer in between
+        assert_eq!(framed.get_ref().bytes, wire.len());
+    }
+
+    #[test]
+    fn frame_buffered_with_greeting() {
+        use std::io::BufRead;
+
+        // The frame arrives with the greeting and the peer then waits for
+        // a reply, which reading the stream again would never see
+        let script = vec![
+            Ok(b"HELLO\n\x01\x07".to_vec()),
+            Err(ErrorKind::Unsupported.into()),
+        ];
+        let mut reader = BufReader::new(ScriptedReader::new(script));
+        let mut greeting = String::new();
+        reader.read_line(&mut greeting).unwrap();
+
+        let mut framed = FramedRead::from_buf_read(reader, TestCodec);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(7));
+        assert_eq!(framed.get_ref().0.len(), 1);
+    }
+
+    #[test]
+    fn into_parts_keeps_leftover_bytes() {
+        let mut wire = vec![];
+        let mut writer = FramedWrite::new(&mut wire, TestCodec);
+        writer.framed_write(TestMsg::U16(1)).unwrap();
+        writer.framed_write(TestMsg::U16(2)).unwrap();
+        drop(writer);
+        let second = wire[3..].to_vec();
+
+        let mut framed = FramedRead::new(&wire[..], TestCodec);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U16(1));
+        let (rest, leftover) = framed.into_parts();
+        assert_eq!(&leftover[..], &second[..]);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn rate_limited_writes() {
+        let clock = FakeClock::new();
+        let start = clock.now();
+        let mut framed = FramedWrite::new(CountingWriter::default(), TestCodec);
+        framed.set_rate_limit_with_clock(Some(30), clock.clone());
+
+        // Twice the budget of 3 byte frames: the first second's worth goes
+        // straight out and the rest has to wait for it
+        for i in 0..20 {
+            framed.framed_write(TestMsg::U16(i)).unwrap();
+        }
+        let elapsed = clock.now() - start;
+        assert!(elapsed > Duration::from_millis(990), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(1010), "{elapsed:?}");
+        assert_eq!(framed.get_ref().data.len(), 60);
+
+        // Idle time refills the bucket
+        clock.advance(Duration::from_secs(5));
+        let before = clock.now();
+        for i in 0..10 {
+            framed.framed_write(TestMsg::U16(i)).unwrap();
+        }
+        assert_eq!(clock.now(), before);
+
+        framed.set_rate_limit(None);
+        for i in 0..100 {
+            framed.framed_write(TestMsg::U16(i)).unwrap();
+        }
+        assert_eq!(clock.now(), before);
+    }
+
+    #[test]
+    fn heartbeat_after_interval() {
+        let clock = FakeClock::new();
+        let mut framed = FramedWrite::new(CountingWriter::default(), TestCodec);
+        assert!(!framed.maybe_send_heartbeat().unwrap());
+        framed
+            .set_heartbeat_with_clock(TestMsg::U8(0), Duration::from_secs(10), clock.clone())
+            .unwrap();
+
+        clock.advance(Duration::from_secs(9));
+        assert!(!framed.maybe_send_heartbeat().unwrap());
+        clock.advance(Duration::from_secs(1));
+        assert!(framed.maybe_send_heartbeat().unwrap());
+        assert!(!framed.maybe_send_heartbeat().unwrap());
+        assert_eq!(framed.get_ref().data, vec![1, 0]);
+
+        // Any other frame also resets the interval
+        clock.advance(Duration::from_secs(8));
+        framed.framed_write(TestMsg::U8(5)).unwrap();
+        clock.advance(Duration::from_secs(8));
+        assert!(!framed.maybe_send_heartbeat().unwrap());
+        clock.advance(Duration::from_secs(2));
+        assert!(framed.maybe_send_heartbeat().unwrap());
+        assert_eq!(framed.get_ref().data, vec![1, 0, 1, 5, 1, 0]);
+
+        framed.clear_heartbeat();
+        clock.advance(Duration::from_secs(60));
+        assert!(!framed.maybe_send_heartbeat().unwrap());
+    }
+
+    /// An encoder that writes nothing for `None`
+    struct OptionalCodec;
+
+    impl Encoder<Option<u8>> for OptionalCodec {
+        type Error = io::Error;
+
+        fn encode(&mut self, item: Option<u8>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+            dst.extend(item);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn empty_frame() {
+        let mut framed = FramedWrite::new(CountingWriter::default(), OptionalCodec);
+        let err = framed.framed_write(None).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(framed.get_ref().writes, 0);
+        assert_eq!(framed.get_ref().flushes, 0);
+        framed.framed_write(Some(3)).unwrap();
+
+        framed.set_allow_empty_frames(true);
+        framed.framed_write(None).unwrap();
+        assert_eq!(framed.get_ref().data, vec![3]);
+    }
+
+    #[test]
+    fn raw_write_then_frames() {
+        let mut framed = FramedWrite::new(CountingWriter::default(), TestCodec);
+        write!(framed, "HELLO").unwrap();
+        framed.framed_write(TestMsg::U8(1)).unwrap();
+        framed.framed_write_buffered(TestMsg::U8(2)).unwrap();
+        framed.write_all(b"!").unwrap();
+        Write::flush(&mut framed).unwrap();
+        assert_eq!(framed.get_ref().data, b"HELLO\x01\x01\x01\x02!");
+        assert_eq!(framed.get_ref().flushes, 2);
+    }
+
+    #[test]
+    fn flush_on_write() {
+        let mut framed = FramedWrite::new(CountingWriter::default(), TestCodec);
+        framed.framed_write(TestMsg::U8(1)).unwrap();
+        assert_eq!(framed.get_ref().flushes, 1);
+
+        framed.set_flush_on_write(false);
+        framed.framed_write(TestMsg::U8(2)).unwrap();
+        framed.framed_write_all([TestMsg::U8(3)]).unwrap();
+        assert_eq!(framed.get_ref().data, vec![1, 1, 1, 2, 1, 3]);
+        assert_eq!(framed.get_ref().writes, 3);
+        assert_eq!(framed.get_ref().flushes, 1);
+
+        framed.flush().unwrap();
+        assert_eq!(framed.get_ref().flushes, 2);
+    }
+
+    #[test]
+    fn write_all_batch() {
+        let mut framed = FramedWrite::new(CountingWriter::default(), TestCodec);
+        framed
+            .framed_write_all([TestMsg::U8(1), TestMsg::U16(1234), TestMsg::U8(2)])
+            .unwrap();
+        assert_eq!(framed.get_ref().data, vec![1, 1, 2, 4, 210, 1, 2]);
+        assert_eq!(framed.get_ref().writes, 1);
+        assert_eq!(framed.get_ref().flushes, 1);
+    }
+
+    #[test]
+    fn write_all_abandons_batch() {
+        let mut framed = FramedWrite::new(CountingWriter::default(), TestCodec);
+        framed.framed_write_buffered(TestMsg::U8(7)).unwrap();
+        let items = [TestMsg::U8(1), TestMsg::Unrecognised, TestMsg::U8(2)];
+        assert!(framed.framed_write_all(items).is_err());
+        assert!(framed.get_ref().data.is_empty());
+        assert_eq!(&framed.write_buffer()[..], &[1, 7]);
+
+        framed.framed_write_all([TestMsg::U8(3)]).unwrap();
+        assert_eq!(framed.get_ref().data, vec![1, 7, 1, 3]);
+    }
+
+    #[test]
+    fn write_buffered() {
+        let mut framed = FramedWrite::new(CountingWriter::default(), TestCodec);
+        for i in 0..5 {
+            framed.framed_write_buffered(TestMsg::U8(i)).unwrap();
+        }
+        assert!(framed.framed_write_buffered(TestMsg::Unrecognised).is_err());
+        assert_eq!(framed.get_ref().writes, 0);
+
+        framed.flush().unwrap();
+        let writer = framed.get_ref();
+        assert_eq!(writer.writes, 1);
+        assert_eq!(writer.flushes, 1);
+        assert_eq!(writer.data, vec![1, 0, 1, 1, 1, 2, 1, 3, 1, 4]);
+    }
+
+    #[test]
+    fn write_after_buffered() {
+        let mut framed = FramedWrite::new(CountingWriter::default(), TestCodec);
+        framed.framed_write_buffered(TestMsg::U8(1)).unwrap();
+        framed.framed_write(TestMsg::U8(2)).unwrap();
+        assert_eq!(framed.get_ref().data, vec![1, 1, 1, 2]);
+        assert_eq!(framed.get_ref().flushes, 1);
+    }
+
+    #[test]
+    fn write_reports_length() {
+        let mut framed = FramedWrite::new(CountingWriter::default(), TestCodec);
+        framed.framed_write_buffered(TestMsg::U8(1)).unwrap();
+        assert_eq!(framed.framed_write_len(TestMsg::U16(1234)).unwrap(), 3);
+        assert_eq!(framed.get_ref().data, vec![1, 1, 2, 4, 210]);
+    }
+
+    #[derive(Default)]
+    struct HintCodec {
+        reallocated: bool,
+    }
+
+    impl Encoder<usize> for HintCodec {
+        type Error = io::Error;
+
+        fn encode(&mut self, item: usize, dst: &mut BytesMut) -> Result<(), Self::Error> {
+            let ptr = dst.as_ptr();
+            for _ in 0..item {
+                dst.put_u8(1);
+            }
+            self.reallocated |= dst.as_ptr() != ptr;
+            Ok(())
+        }
+
+        fn size_hint(&self, item: &usize) -> Option<usize> {
+            Some(*item)
+        }
+    }
+
+    #[test]
+    fn write_uses_size_hint() {
+        let mut framed = FramedWrite::with_capacity(Vec::new(), HintCodec::default(), 0);
+        framed.framed_write(20000).unwrap();
+        framed.framed_write_buffered(100).unwrap();
+        framed.framed_write_buffered(30000).unwrap();
+        assert!(!framed.encoder().reallocated);
+        framed.flush().unwrap();
+        assert_eq!(framed.get_ref().len(), 50100);
+    }
+
+    #[test]
+    fn write_reuses_buffer() {
+        let mut framed = FramedWrite::with_capacity(Vec::new(), BlobCodec, 0);
+        framed.framed_write(vec![7; 200]).unwrap();
+        let ptr = framed.write_buffer().as_ptr();
+        let capacity = framed.write_buffer().capacity();
+        assert!(capacity >= 201);
+
+        for i in 0..100 {
+            framed.framed_write(vec![i; 200]).unwrap();
+            assert!(framed.write_buffer().is_empty());
+            assert_eq!(framed.write_buffer().as_ptr(), ptr);
+            assert_eq!(framed.write_buffer().capacity(), capacity);
+        }
+        assert_eq!(framed.get_ref().len(), 101 * 201);
+    }
+
+    #[test]
+    fn invalid_write() {
+        let mut buf = vec![];
+        let mut framed = FramedWrite::new(&mut buf, TestCodec);
+        let response = framed.framed_write(TestMsg::Unrecognised);
+        assert!(response.is_err());
+    }
+
+    #[test]
+    fn framed() {
+        let reader = vec![2u8, 4, 210];
+        let mut writer = vec![];
+        let mut framed = Framed::new(&reader[..], &mut writer, TestCodec, TestCodec);
+        framed.framed_write(TestMsg::U16(1234)).ok();
+        let msg = framed.framed_read().unwrap();
+        drop(framed);
+        assert_eq!(writer, vec![2u8, 4, 210]);
+        assert_eq!(msg, TestMsg::U16(1234));
+    }
+
+    /// A reader that records the length of each slice it is asked to fill
+    struct RecordingReader {
+        data: Vec<u8>,
+        requested: Vec<usize>,
+    }
+
+    impl Read for RecordingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.requested.push(buf.len());
+            let n = buf.len().min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data.drain(..n);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn read_chunk_size() {
+        let reader = RecordingReader {
+            data: vec![2, 4, 210, 2, 4, 210],
+            requested: vec![],
+        };
+        let mut reader = FramedRead::with_capacity(reader, TestCodec, 64);
+        reader.set_read_chunk_size(2);
+        assert_eq!(reader.framed_read().unwrap(), TestMsg::U16(1234));
+        assert_eq!(reader.get_ref().requested, vec![2, 2]);
+
+        reader.set_read_chunk_size(4096);
+        assert_eq!(reader.framed_read().unwrap(), TestMsg::U16(1234));
+        assert_eq!(reader.get_ref().requested[2], 4096);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_read_chunk_size() {
+        FramedRead::new(&[0u8][..], TestCodec).set_read_chunk_size(0);
+    }
+
+    #[test]
+    fn read_once_per_call() {
+        let reader = ScriptedReader::new(vec![Ok(vec![2, 4]), Ok(vec![210, 1])]);
+        let mut framed = FramedRead::new(reader, TestCodec);
+        assert_eq!(framed.framed_read_once().unwrap(), None);
+        assert_eq!(&framed.read_buffer()[..], &[2, 4]);
+        assert_eq!(framed.framed_read_once().unwrap(), Some(TestMsg::U16(1234)));
+        assert_eq!(&framed.read_buffer()[..], &[1]);
+        assert!(framed.framed_read_once().is_err());
+    }
+
+    #[test]
+    fn read_batch() {
+        let reader = ScriptedReader::new(vec![
+            Ok(vec![1, 1, 2, 4, 210, 1, 3, 2]),
+            Ok(vec![0]),
+            Ok(vec![5]),
+        ]);
+        let mut framed = FramedRead::new(reader, TestCodec);
+        assert_eq!(
+            framed.framed_read_batch(None).unwrap(),
+            vec![TestMsg::U8(1), TestMsg::U16(1234), TestMsg::U8(3)]
+        );
+        assert!(framed.framed_read_batch(None).unwrap().is_empty());
+        assert_eq!(
+            framed.framed_read_batch(None).unwrap(),
+            vec![TestMsg::U16(5)]
+        );
+        let err = framed.framed_read_batch(None).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ConnectionReset);
+    }
+
+    /// A reader that hands out its data in one read and panics if read again
+    struct ReadOnce(Option<Vec<u8>>);
+
+    impl Read for ReadOnce {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let data = self.0.take().expect("read a second time");
+            buf[..data.len()].copy_from_slice(&data);
+            Ok(data.len())
+        }
+    }
+
+    #[test]
+    fn read_batch_of_buffered_frames() {
+        let mut framed = FramedRead::new(ReadOnce(Some(vec![1, 1, 1, 2, 1, 3])), TestCodec);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(1));
+        assert_eq!(
+            framed.framed_read_batch(None).unwrap(),
+            [TestMsg::U8(2), TestMsg::U8(3)]
+        );
+    }
+
+    #[test]
+    fn read_batch_limited() {
+        let r = [1u8, 1, 1, 2, 1, 3, 1, 4, 1, 5];
+        let mut framed = FramedRead::new(&r[..], TestCodec);
+        let sizes: Vec<usize> = (0..3)
+            .map(|_| framed.framed_read_batch(Some(2)).unwrap().len())
+            .collect();
+        assert_eq!(sizes, [2, 2, 1]);
+        assert!(framed.framed_read_batch(Some(2)).is_err());
+    }
+
+    #[test]
+    fn zero_read_batch_size() {
+        let mut framed = FramedRead::new(&[1u8, 1][..], TestCodec);
+        let err = framed.framed_read_batch(Some(0)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        // Nothing was read
+        assert_eq!(framed.framed_read_batch(Some(1)).unwrap(), [TestMsg::U8(1)]);
+    }
+
+    #[test]
+    fn seek_discards_buffer() {
+        use std::io::Cursor;
+
+        let mut framed = FramedRead::new(Cursor::new(vec![1u8, 7, 2, 4, 210]), TestCodec);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(7));
+        assert_eq!(framed.seek(SeekFrom::Start(0)).unwrap(), 0);
+        assert!(framed.read_buffer().is_empty());
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(7));
+        // The current position is that of the next undecoded frame
+        assert_eq!(framed.seek(SeekFrom::Current(0)).unwrap(), 2);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U16(1234));
+    }
+
+    #[test]
+    fn codec_by_mut_ref() {
+        let mut codec = TestCodec;
+        let mut framed = FramedWrite::new(vec![], &mut codec);
+        framed.framed_write(TestMsg::U8(7)).unwrap();
+        let wire = framed.into_inner();
+
+        // The same codec drives one connection after another
+        for _ in 0..2 {
+            let mut framed = FramedRead::new(&wire[..], &mut codec);
+            assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(7));
+        }
+    }
+
+    #[test]
+    fn set_decoder_keeps_buffer() {
+        use crate::codec::FixedLengthCodec;
+
+        let mut framed = FramedRead::new(&b"abcdef"[..], FixedLengthCodec::new(2));
+        assert_eq!(&framed.framed_read().unwrap()[..], b"ab");
+        let old = framed.set_decoder(FixedLengthCodec::new(4));
+        assert_eq!(old.record_len(), 2);
+        assert_eq!(&framed.peek_frame().unwrap().unwrap()[..], b"cdef");
+    }
+
+    #[test]
+    fn read_opt_outcomes() {
+        let r = [1u8, 7];
+        let mut reader = FramedRead::new(&r[..], TestCodec);
+        assert_eq!(reader.framed_read_opt().unwrap(), Some(TestMsg::U8(7)));
+        assert_eq!(reader.framed_read_opt().unwrap(), None);
+        assert_eq!(reader.framed_read_opt().unwrap(), None);
+
+        let r = [1u8, 7, 2, 4];
+        let mut reader = FramedRead::new(&r[..], TestCodec);
+        assert_eq!(reader.framed_read_opt().unwrap(), Some(TestMsg::U8(7)));
+        let err = reader.framed_read_opt().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ConnectionReset);
+
+        let reader = ScriptedReader::new(vec![Ok(vec![1]), Err(ErrorKind::BrokenPipe.into())]);
+        let mut reader = FramedRead::new(reader, TestCodec);
+        let err = reader.framed_read_opt().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn fill_buf_then_drain() {
+        let r = [1u8, 1, 2, 4, 210, 1, 3, 2, 0];
+        let mut reader = FramedRead::new(&r[..], TestCodec);
+        assert_eq!(reader.fill_buf().unwrap(), 9);
+        assert_eq!(reader.peek_frame().unwrap(), Some(TestMsg::U8(1)));
+        assert_eq!(reader.peek_frame().unwrap(), Some(TestMsg::U16(1234)));
+        assert_eq!(reader.peek_frame().unwrap(), Some(TestMsg::U8(3)));
+        assert_eq!(reader.peek_frame().unwrap(), None);
+        assert_eq!(&reader.read_buffer()[..], &[2, 0]);
+        assert_eq!(reader.fill_buf().unwrap(), 0);
+    }
+
+    #[test]
+    fn tee_captures_wire_bytes() {
+        let r = [2u8, 4, 210, 1, 9, 2];
+        let mut reader = FramedRead::with_capacity(&r[..], TestCodec, 2).tee(vec![]);
+        assert_eq!(reader.framed_read().unwrap(), TestMsg::U16(1234));
+        assert_eq!(reader.framed_read().unwrap(), TestMsg::U8(9));
+        assert!(reader.framed_read().is_err());
+        assert_eq!(reader.get_ref().sink(), &r);
+        let (inner, sink) = reader.into_inner().into_inner();
+        assert!(inner.is_empty());
+        assert_eq!(sink, r);
+    }
+
+    #[test]
+    fn read_with_len() {
+        let r = [2u8, 4, 210, 1, 7];
+        let mut reader = FramedRead::new(&r[..], TestCodec);
+        assert_eq!(
+            reader.framed_read_with_len().unwrap(),
+            (TestMsg::U16(1234), 3)
+        );
+        assert_eq!(reader.framed_read_with_len().unwrap(), (TestMsg::U8(7), 2));
+    }
+
+    #[test]
+    fn from_tuples() {
+        let r = [2u8, 4, 210];
+        let mut reader: FramedRead<_, _> = (&r[..], TestCodec).into();
+        assert_eq!(reader.read_buffer().capacity(), INITIAL_CAPACITY);
+        assert_eq!(reader.framed_read().unwrap(), TestMsg::U16(1234));
+
+        let mut writer: FramedWrite<_, _> = (vec![], TestCodec).into();
+        writer.framed_write(TestMsg::U16(1234)).unwrap();
+        assert_eq!(writer.get_ref(), &r);
+
+        let mut written = vec![];
+        let mut framed: Framed<_, _, _, _> = (&r[..], &mut written, TestCodec, TestCodec).into();
+        framed.framed_write(TestMsg::U8(1)).unwrap();
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U16(1234));
+        drop(framed);
+        assert_eq!(written, vec![1, 1]);
+    }
+
+    #[test]
+    fn framed_with_capacities() {
+        let framed = Framed::with_capacities(&[][..], vec![], TestCodec, TestCodec, 16, 4096);
+        assert_eq!(framed.read_buffer().capacity(), 16);
+        assert_eq!(framed.write_buffer().capacity(), 4096);
+
+        let framed = Framed::new(&[][..], vec![], TestCodec, TestCodec);
+        assert_eq!(framed.read_buffer().capacity(), INITIAL_CAPACITY);
+        assert_eq!(framed.write_buffer().capacity(), INITIAL_CAPACITY);
+    }
+
+    #[test]
+    fn framed_with_codec() {
+        let reader = [2u8, 4, 210];
+        let mut writer = vec![];
+        let mut framed = Framed::with_codec(&reader[..], &mut writer, TestCodec);
+        framed.framed_write(TestMsg::U16(1234)).ok();
+        let msg = framed.framed_read().unwrap();
+        drop(framed);
+        assert_eq!(writer, vec![2u8, 4, 210]);
+        assert_eq!(msg, TestMsg::U16(1234));
+    }
+
+    #[test]
+    fn inner_accessors() {
+        let r = [1u8, 12];
+        let mut reader = FramedRead::new(&r[..], TestCodec);
+        assert_eq!(reader.get_ref(), &[1u8, 12]);
+        assert_eq!(reader.framed_read().unwrap(), TestMsg::U8(12));
+        assert!(reader.get_mut().is_empty());
+        assert!(reader.into_inner().is_empty());
+
+        let mut writer = FramedWrite::new(vec![], TestCodec);
+        writer.framed_write(TestMsg::U8(12)).unwrap();
+        writer.get_mut().push(0);
+        assert_eq!(writer.get_ref(), &vec![1, 12, 0]);
+        assert_eq!(writer.into_inner(), vec![1, 12, 0]);
+    }
+
+    #[test]
+    fn inspect_read_buffer() {
+        let r = [1u8, 12, 2, 4];
+        let mut framed = FramedRead::new(&r[..], TestCodec);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(12));
+        assert_eq!(&framed.read_buffer()[..], &[2, 4]);
+
+        framed.read_buffer_mut().extend_from_slice(&[210]);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U16(1234));
+        assert!(framed.read_buffer().is_empty());
+    }
+
+    #[test]
+    fn framed_inner_accessors() {
+        let reader = [2u8, 4, 210];
+        let mut framed = Framed::new(&reader[..], vec![], TestCodec, TestCodec);
+        framed.framed_write(TestMsg::U16(1234)).unwrap();
+        assert_eq!(framed.get_ref().1, &vec![2, 4, 210]);
+        framed.get_mut().1.clear();
+        let (r, w) = framed.into_inner();
+        assert_eq!(r, &[2u8, 4, 210]);
+        assert!(w.is_empty());
+    }
+
+    #[derive(Debug)]
+    enum ProtoError {
+        Io(io::Error),
+        BadTag(u8),
+    }
+
+    impl From<io::Error> for ProtoError {
+        fn from(e: io::Error) -> Self {
+            ProtoError::Io(e)
+        }
+    }
+
+    struct TaggedCodec;
+
+    impl Decoder for TaggedCodec {
+        type Item = u8;
+        type Error = ProtoError;
+
+        fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+            if src.len() < 2 {
+                return Ok(None);
+            }
+            match src.get_u8() {
+                0xAA => Ok(Some(src.get_u8())),
+                tag => Err(ProtoError::BadTag(tag)),
+            }
+        }
+    }
+
+    impl Encoder<u8> for TaggedCodec {
+        type Error = ProtoError;
+
+        fn encode(&mut self, item: u8, dst: &mut BytesMut) -> Result<(), Self::Error> {
+            if item == 0 {
+                return Err(ProtoError::BadTag(0));
+            }
+            dst.put_u8(0xAA);
+            dst.put_u8(item);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn custom_error_type() {
+        let r = [0xAAu8, 7, 0x55, 1];
+        let mut reader = FramedRead::new(&r[..], TaggedCodec);
+        assert_eq!(reader.framed_read().unwrap(), 7);
+        assert!(matches!(
+            reader.framed_read(),
+            Err(ProtoError::BadTag(0x55))
+        ));
+        assert!(matches!(
+            reader.framed_read(),
+            Err(ProtoError::Io(e)) if e.kind() == ErrorKind::Other
+        ));
+
+        let mut buf = vec![];
+        let mut writer = FramedWrite::new(&mut buf, TaggedCodec);
+        writer.framed_write(9).unwrap();
+        assert!(matches!(writer.framed_write(0), Err(ProtoError::BadTag(0))));
+        drop(writer);
+        assert_eq!(buf, vec![0xAA, 9]);
+    }
+
+    #[test]
+    fn framed_over_tcp() {
+        let test_buf = vec![2u8, 25, 143];
+
+        let tcp_server_mocker = ServerMocker::tcp_with_port(35642).unwrap();
+        let rx = TcpStream::connect("127.0.0.1:35642").unwrap();
+        let tx = rx.try_clone().unwrap();
+
+        let mut framed = Framed::new(rx, tx, TestCodec, TestCodec);
+
+        tcp_server_mocker
+            .add_mock_instructions(vec![
                 ReceiveMessage,
                 SendMessage(Vec::from(test_buf.clone())),
             ])
             .unwrap();
 
-        framed.framed_write(TestMsg::U16(6543)).ok();
-        let response = framed.framed_read().unwrap();
+        framed.framed_write(TestMsg::U16(6543)).ok();
+        let response = framed.framed_read().unwrap();
+
+        assert_eq!(response, TestMsg::U16(6543));
+        assert_eq!(tcp_server_mocker.pop_received_message().unwrap(), test_buf);
+    }
+
+    #[test]
+    fn read_timeout_resumes_partial_frame() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(&[2, 4]).unwrap();
+            thread::sleep(Duration::from_millis(300));
+            stream.write_all(&[210]).unwrap();
+        });
+
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut framed = FramedRead::new(stream, TestCodec);
+        let err = framed
+            .framed_read_timeout(Duration::from_millis(100))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert_eq!(&framed.read_buffer()[..], &[2, 4]);
+
+        let msg = framed.framed_read_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(msg, TestMsg::U16(1234));
+        assert_eq!(framed.get_ref().read_timeout().unwrap(), None);
+        server.join().unwrap();
+    }
+
+    fn slow_server(chunks: Vec<Vec<u8>>, pause: Duration) -> (TcpStream, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            for chunk in chunks {
+                thread::sleep(pause);
+                stream.write_all(&chunk).unwrap();
+            }
+            thread::sleep(pause);
+        });
+        (TcpStream::connect(addr).unwrap(), server)
+    }
+
+    #[test]
+    fn read_deadline_across_chunks() {
+        let (stream, server) =
+            slow_server(vec![vec![2], vec![4], vec![210]], Duration::from_millis(50));
+        let mut framed = FramedRead::new(stream, TestCodec);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        assert_eq!(
+            framed.framed_read_deadline(deadline).unwrap(),
+            TestMsg::U16(1234)
+        );
+        assert!(Instant::now() < deadline);
+        assert_eq!(framed.get_ref().read_timeout().unwrap(), None);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn read_deadline_expires() {
+        let (stream, server) = slow_server(vec![vec![2, 4], vec![210]], Duration::from_millis(150));
+        let mut framed = FramedRead::new(stream, TestCodec);
+        // Each chunk arrives within the per-read limit but not the whole frame
+        let start = Instant::now();
+        let err = framed
+            .framed_read_deadline(start + Duration::from_millis(200))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_millis(290));
+        assert_eq!(&framed.read_buffer()[..], &[2, 4]);
+
+        let msg = framed
+            .framed_read_deadline(Instant::now() + Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(msg, TestMsg::U16(1234));
+        server.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn raw_fd_of_inner_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let fd = stream.as_raw_fd();
+        let framed = Framed::from_tcp(stream, TestCodec, TestCodec).unwrap();
+        assert_eq!(framed.as_raw_fd(), fd);
+        let (reader, writer) = framed.split();
+        assert_eq!(reader.as_raw_fd(), fd);
+        assert_eq!(writer.as_raw_fd(), writer.get_ref().as_raw_fd());
+        assert_ne!(writer.as_raw_fd(), fd);
+    }
 
-        assert_eq!(response, TestMsg::U16(6543));
-        assert_eq!(tcp_server_mocker.pop_received_message().unwrap(), test_buf);
+    #[test]
+    fn borrow_tcp_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(&[1, 42]).unwrap();
+            let mut request = [0; 2];
+            stream.read_exact(&mut request).unwrap();
+            assert_eq!(request, [1, 1]);
+            stream.write_all(b"trailer").unwrap();
+        });
+        let mut stream = TcpStream::connect(addr).unwrap();
+
+        let mut framed = FramedRead::new(&mut stream, TestCodec);
+        assert_eq!(
+            framed.framed_read_timeout(Duration::from_secs(5)).unwrap(),
+            TestMsg::U8(42)
+        );
+        drop(framed);
+
+        let mut framed = FramedWrite::new(&mut stream, TestCodec);
+        framed.framed_write(TestMsg::U8(1)).unwrap();
+        drop(framed);
+
+        // The stream is ours again once the framed wrappers are gone
+        let mut trailer = vec![];
+        stream.read_to_end(&mut trailer).unwrap();
+        assert_eq!(trailer, b"trailer");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn shutdown_write_after_final_frame() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![];
+            // Only returns once the client has shut down its writing half
+            stream.read_to_end(&mut request).unwrap();
+            stream.write_all(&[1, request.len() as u8]).unwrap();
+            request
+        });
+
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut framed = Framed::from_tcp(stream, TestCodec, TestCodec).unwrap();
+        framed.framed_write_buffered(TestMsg::U8(7)).unwrap();
+        framed.framed_write_buffered(TestMsg::U16(1234)).unwrap();
+        framed.shutdown_write().unwrap();
+        assert_eq!(server.join().unwrap(), vec![1, 7, 2, 4, 210]);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(5));
+    }
+
+    #[test]
+    fn tcp_options_applied() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let options = TcpOptions::new()
+            .read_timeout(Some(Duration::from_secs(3)))
+            .write_timeout(Some(Duration::from_secs(4)))
+            .nodelay(true);
+        let framed = Framed::from_tcp_with(stream, TestCodec, TestCodec, options).unwrap();
+        let (reader, writer) = framed.get_ref();
+        for stream in [reader, writer] {
+            assert_eq!(stream.read_timeout().unwrap(), Some(Duration::from_secs(3)));
+            assert_eq!(
+                stream.write_timeout().unwrap(),
+                Some(Duration::from_secs(4))
+            );
+            assert!(stream.nodelay().unwrap());
+        }
+
+        // Unset options are left alone
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        stream.set_nodelay(true).unwrap();
+        TcpOptions::new()
+            .read_timeout(Some(Duration::from_secs(1)))
+            .apply(&stream)
+            .unwrap();
+        assert!(stream.nodelay().unwrap());
+        assert_eq!(stream.write_timeout().unwrap(), None);
+    }
+
+    #[test]
+    fn try_clone_reader() {
+        let server = ServerMocker::tcp().unwrap();
+        let stream = TcpStream::connect(server.socket_address()).unwrap();
+        let mut reader = FramedRead::new(stream, TestCodec);
+        reader.set_max_buffer_size(Some(64));
+        reader.set_max_empty_reads(3);
+        let mut clone = reader.try_clone().unwrap();
+        assert!(clone.read_buffer().is_empty());
+        assert_eq!(clone.max_buffer_size, Some(64));
+        assert_eq!(clone.max_empty_reads, 3);
+
+        server
+            .add_mock_instructions(vec![SendMessage(vec![1, 5]), SendMessage(vec![1, 6])])
+            .unwrap();
+        // Whichever reader reads first gets the first frame off the socket
+        assert_eq!(reader.framed_read().unwrap(), TestMsg::U8(5));
+        let msg = match reader.peek_frame().unwrap() {
+            Some(msg) => msg,
+            None => clone.framed_read().unwrap(),
+        };
+        assert_eq!(msg, TestMsg::U8(6));
+    }
+
+    #[test]
+    fn iterate_frames() {
+        let r = [1u8, 1, 1, 2, 2, 4, 210];
+        let mut frames = FramedRead::new(&r[..], TestCodec).iter();
+        assert_eq!(frames.next().unwrap().unwrap(), TestMsg::U8(1));
+        assert_eq!(frames.next().unwrap().unwrap(), TestMsg::U8(2));
+        assert_eq!(frames.next().unwrap().unwrap(), TestMsg::U16(1234));
+        assert!(frames.next().is_none());
+        assert!(frames.next().is_none());
+    }
+
+    #[test]
+    fn iterate_frames_truncated() {
+        let r = [1u8, 1, 2, 4];
+        let frames: Vec<_> = FramedRead::new(&r[..], TestCodec).into_iter().collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].as_ref().unwrap(), &TestMsg::U8(1));
+        assert!(frames[1].is_err());
+    }
+
+    #[test]
+    fn read_buffer_shrinks() {
+        let mut data = vec![];
+        data.extend_from_slice(&(1024u32 * 1024).to_be_bytes());
+        data.extend_from_slice(&[7; 1024 * 1024]);
+        for _ in 0..100 {
+            data.extend_from_slice(&[0, 0, 0, 1, 9]);
+        }
+
+        let reader = ChunkedReader {
+            data,
+            pos: 0,
+            chunk: 64 * 1024,
+        };
+        let mut framed = FramedRead::with_capacity(reader, U32LenCodec, 1024);
+        assert_eq!(framed.framed_read().unwrap().len(), 1024 * 1024);
+        for _ in 0..100 {
+            assert_eq!(framed.framed_read().unwrap(), vec![9]);
+        }
+        assert!(framed.read_buffer().capacity() <= 8 * 1024);
+    }
+
+    #[test]
+    fn read_buffer_shrink_disabled() {
+        let mut data = vec![];
+        data.extend_from_slice(&(1024u32 * 1024).to_be_bytes());
+        data.extend_from_slice(&[7; 1024 * 1024]);
+        data.extend_from_slice(&[0, 0, 0, 1, 9]);
+
+        let reader = ChunkedReader {
+            data,
+            pos: 0,
+            chunk: 64 * 1024,
+        };
+        let mut framed = FramedRead::with_capacity(reader, U32LenCodec, 1024);
+        framed.set_shrink_threshold(None);
+        assert_eq!(framed.framed_read().unwrap().len(), 1024 * 1024);
+        assert_eq!(framed.framed_read().unwrap(), vec![9]);
+        assert!(framed.read_buffer().capacity() > 8 * 1024);
+    }
+
+    /// An in-memory stream that reads from one buffer and writes to another
+    struct Duplex {
+        input: io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn framed_from_stream() {
+        let stream = Duplex {
+            input: io::Cursor::new(vec![2, 4, 210]),
+            output: vec![],
+        };
+        let mut framed = Framed::from_stream(stream, TestCodec);
+        framed.framed_write(TestMsg::U8(12)).unwrap();
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U16(1234));
+
+        let (reader, writer) = framed.into_inner();
+        drop(reader);
+        let stream = writer.try_unwrap().ok().unwrap();
+        assert_eq!(stream.output, vec![1, 12]);
+    }
+
+    #[test]
+    fn debug_output() {
+        let r = [1u8, 12, 2, 4];
+        let mut reader = FramedRead::new(&r[..], TestCodec);
+        reader.framed_read().unwrap();
+        let out = format!("{:?}", reader);
+        assert!(out.contains("buffer_len: 2"), "{}", out);
+        assert!(out.contains("pending: b\"\\x02\\x04\""), "{}", out);
+
+        reader.read_buffer_mut().extend_from_slice(&[0; 64]);
+        let out = format!("{:?}", reader);
+        assert!(out.contains("buffer_len: 66"), "{}", out);
+        assert!(out.contains("..."), "{}", out);
+
+        let mut writer = FramedWrite::new(Vec::<u8>::new(), TestCodec);
+        writer.framed_write_buffered(TestMsg::U8(1)).unwrap();
+        let out = format!("{:?}", writer);
+        assert!(out.contains("buffer_len: 2"), "{}", out);
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Payload {
+        data: Vec<u8>,
+    }
+
+    struct PayloadCodec;
+
+    impl Decoder for PayloadCodec {
+        type Item = Payload;
+        type Error = io::Error;
+
+        fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+            let mut item = Payload::default();
+            Ok(self.decode_into(src, &mut item)?.then_some(item))
+        }
+
+        fn decode_into(
+            &mut self,
+            src: &mut BytesMut,
+            dst: &mut Self::Item,
+        ) -> Result<bool, Self::Error> {
+            if src.is_empty() || src.len() < src[0] as usize + 1 {
+                return Ok(false);
+            }
+            let len = src.get_u8() as usize;
+            dst.data.clear();
+            dst.data.extend_from_slice(&src.split_to(len));
+            Ok(true)
+        }
+    }
+
+    #[test]
+    fn read_into_reuses_item() {
+        let r = [3u8, 1, 2, 3, 2, 4, 5];
+        let mut framed = FramedRead::new(&r[..], PayloadCodec);
+        let mut item = Payload {
+            data: Vec::with_capacity(16),
+        };
+        let ptr = item.data.as_ptr();
+
+        framed.framed_read_into(&mut item).unwrap();
+        assert_eq!(item.data, vec![1, 2, 3]);
+        framed.framed_read_into(&mut item).unwrap();
+        assert_eq!(item.data, vec![4, 5]);
+        assert_eq!(item.data.as_ptr(), ptr);
+        assert!(framed.framed_read_into(&mut item).is_err());
+
+        // Also through a codec shared with another framed object
+        let mut framed = FramedRead::new(&r[..], Shared::new(PayloadCodec));
+        framed.framed_read_into(&mut item).unwrap();
+        assert_eq!(item.data, vec![1, 2, 3]);
+        assert_eq!(item.data.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn peek_buffered_frames() {
+        let r = [1u8, 5];
+        let mut framed = FramedRead::new(&r[..], TestCodec);
+        assert!(framed.peek_frame().unwrap().is_none());
+
+        framed
+            .read_buffer_mut()
+            .extend_from_slice(&[1, 12, 2, 4, 210, 2]);
+        assert_eq!(framed.peek_frame().unwrap(), Some(TestMsg::U8(12)));
+        assert_eq!(framed.peek_frame().unwrap(), Some(TestMsg::U16(1234)));
+        assert!(framed.peek_frame().unwrap().is_none());
+        assert_eq!(&framed.read_buffer()[..], &[2]);
+        assert_eq!(framed.get_ref(), &[1u8, 5]);
+    }
+
+    /// Splits the stream into records of `width` bytes
+    struct WidthCodec {
+        width: usize,
+    }
+
+    impl Decoder for WidthCodec {
+        type Item = Vec<u8>;
+        type Error = io::Error;
+
+        fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+            if src.len() < self.width {
+                return Ok(None);
+            }
+            Ok(Some(src.split_to(self.width).to_vec()))
+        }
+    }
+
+    impl Encoder<Vec<u8>> for WidthCodec {
+        type Error = io::Error;
+
+        fn encode(&mut self, item: Vec<u8>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+            dst.extend_from_slice(&item[..self.width]);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn mutate_codec_state() {
+        let r = [1u8, 2, 3, 4, 5, 6];
+        let mut reader = FramedRead::new(&r[..], WidthCodec { width: 2 });
+        assert_eq!(reader.framed_read().unwrap(), vec![1, 2]);
+        reader.decoder_mut().width = 4;
+        assert_eq!(reader.decoder().width, 4);
+        assert_eq!(reader.framed_read().unwrap(), vec![3, 4, 5, 6]);
+
+        let mut framed = Framed::new(
+            &r[..],
+            vec![],
+            WidthCodec { width: 1 },
+            WidthCodec { width: 1 },
+        );
+        framed.framed_write(vec![9, 9, 9]).unwrap();
+        framed.encoder_mut().width = 3;
+        framed.framed_write(vec![9, 9, 9]).unwrap();
+        assert_eq!(framed.encoder().width, 3);
+        assert_eq!(framed.decoder().width, 1);
+        assert_eq!(framed.get_ref().1, &vec![9, 9, 9, 9]);
+    }
+
+    #[test]
+    fn switch_decoder_keeps_buffer() {
+        let r = [1u8, 12, 4, 1, 2, 3, 4];
+        let mut reader = FramedRead::new(&r[..], TestCodec);
+        assert_eq!(reader.framed_read().unwrap(), TestMsg::U8(12));
+        assert_eq!(reader.read_buffer().len(), 5);
+
+        let mut reader = reader.map_decoder(|_| WidthCodec { width: 5 });
+        assert_eq!(reader.framed_read().unwrap(), vec![4, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn switch_encoder_keeps_buffer() {
+        let mut writer = FramedWrite::new(Vec::new(), TestCodec);
+        writer.framed_write_buffered(TestMsg::U8(1)).unwrap();
+        let mut writer = writer.map_encoder(|_| BlobCodec);
+        writer.framed_write(vec![5, 6]).unwrap();
+        assert_eq!(writer.get_ref(), &vec![1, 1, 2, 5, 6]);
+    }
+
+    #[test]
+    fn poisoned_after_decode_error() {
+        let r = [0xAAu8, 7, 0x55, 1, 0xAA, 8];
+        let mut reader = FramedRead::with_capacity(&r[..], TaggedCodec, 2);
+        assert_eq!(reader.framed_read().unwrap(), 7);
+        assert!(matches!(
+            reader.framed_read(),
+            Err(ProtoError::BadTag(0x55))
+        ));
+        for _ in 0..2 {
+            assert!(matches!(
+                reader.framed_read(),
+                Err(ProtoError::Io(e)) if e.kind() == ErrorKind::Other
+            ));
+        }
+        assert!(reader.peek_frame().is_err());
+        assert_eq!(reader.get_ref(), &[0xAAu8, 8]);
+    }
+
+    #[test]
+    fn reset_after_decode_error() {
+        let r = [0x55u8, 1, 0xAA, 8];
+        let mut reader = FramedRead::with_capacity(&r[..], TaggedCodec, 2);
+        assert!(matches!(
+            reader.framed_read(),
+            Err(ProtoError::BadTag(0x55))
+        ));
+        assert!(reader.is_poisoned());
+        reader.reset();
+        assert!(!reader.is_poisoned());
+        assert!(reader.read_buffer().is_empty());
+        assert_eq!(reader.framed_read().unwrap(), 8);
+    }
+
+    /// A writer that fails once it holds `limit` bytes
+    struct LimitWriter {
+        data: Vec<u8>,
+        limit: usize,
+    }
+
+    impl Write for LimitWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.limit - self.data.len());
+            if n == 0 {
+                return Err(ErrorKind::BrokenPipe.into());
+            }
+            self.data.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn poisoned_after_write_error() {
+        let writer = LimitWriter {
+            data: vec![],
+            limit: 4,
+        };
+        let mut writer = FramedWrite::new(writer, TestCodec);
+        writer.framed_write(TestMsg::U8(1)).unwrap();
+        assert!(!writer.is_poisoned());
+        let err = writer.framed_write(TestMsg::U16(1234)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+        assert!(writer.is_poisoned());
+        assert_eq!(writer.get_ref().data, vec![1, 1, 2, 4]);
+
+        writer.reset();
+        assert!(!writer.is_poisoned());
+        assert!(writer.write_buffer().is_empty());
+    }
+
+    /// A writer that accepts every write but fails to flush
+    struct FailingFlush(Vec<u8>);
+
+    impl Write for FailingFlush {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn poisoned_after_vectored_flush_error() {
+        let mut writer = FramedWrite::new(FailingFlush(vec![]), TestCodec);
+        writer.set_flush_on_write(true);
+        let err = writer.framed_write_vectored(TestMsg::U8(1)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+        assert!(writer.is_poisoned());
+        assert_eq!(writer.get_ref().0, vec![1, 1]);
+    }
+
+    struct NeverCodec;
+
+    impl Decoder for NeverCodec {
+        type Item = ();
+        type Error = io::Error;
+
+        fn decode(&mut self, _src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn max_buffer_size_guard() {
+        let mut framed = FramedRead::new(io::repeat(1), NeverCodec);
+        framed.set_max_buffer_size(Some(20 * 1024));
+        let err = framed.framed_read().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(framed.read_buffer().len(), 20 * 1024);
+    }
+
+    #[test]
+    fn max_buffer_size_allows_frames() {
+        let r = [2u8, 4, 210, 1, 12];
+        let mut framed = FramedRead::new(&r[..], TestCodec);
+        framed.set_max_buffer_size(Some(3));
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U16(1234));
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(12));
+    }
+
+    #[test]
+    fn connect_round_trip() {
+        let tcp_server_mocker = ServerMocker::tcp().unwrap();
+        let mut framed = Framed::connect(tcp_server_mocker.socket_address(), TestCodec).unwrap();
+
+        tcp_server_mocker
+            .add_mock_instructions(vec![ReceiveMessage, SendMessage(vec![1, 42])])
+            .unwrap();
+        framed.framed_write(TestMsg::U8(7)).unwrap();
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(42));
+        assert_eq!(
+            tcp_server_mocker.pop_received_message().unwrap(),
+            vec![1, 7]
+        );
+    }
+
+    #[test]
+    fn accept_round_trip() {
+        let mut listener = FramedListener::bind("127.0.0.1:0", TestCodec).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut framed = Framed::connect(addr, TestCodec).unwrap();
+            framed.framed_write(TestMsg::U8(7)).unwrap();
+            framed.framed_read().unwrap()
+        });
+
+        let mut framed = listener.next().unwrap().unwrap();
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(7));
+        framed.framed_write(TestMsg::U16(1234)).unwrap();
+        assert_eq!(client.join().unwrap(), TestMsg::U16(1234));
+    }
+
+    #[test]
+    fn split_tcp_across_threads() {
+        fn assert_send<T: Send>(_: &T) {}
+
+        let tcp_server_mocker = ServerMocker::tcp().unwrap();
+        let stream = TcpStream::connect(tcp_server_mocker.socket_address()).unwrap();
+        let (mut rx, mut tx) = Framed::split_tcp(stream, TestCodec, TestCodec).unwrap();
+        assert_send(&rx);
+        assert_send(&tx);
+
+        tcp_server_mocker
+            .add_mock_instructions(vec![ReceiveMessage, SendMessage(vec![1, 99])])
+            .unwrap();
+
+        let reader = thread::spawn(move || rx.framed_read().unwrap());
+        let writer = thread::spawn(move || tx.framed_write(TestMsg::U8(5)).unwrap());
+
+        writer.join().unwrap();
+        assert_eq!(reader.join().unwrap(), TestMsg::U8(99));
+        assert_eq!(
+            tcp_server_mocker.pop_received_message().unwrap(),
+            vec![1, 5]
+        );
+    }
+
+    /// A reader that returns a scripted sequence of results, then EOF
+    struct ScriptedReader(VecDeque<io::Result<Vec<u8>>>);
+
+    impl ScriptedReader {
+        fn new(script: Vec<io::Result<Vec<u8>>>) -> Self {
+            Self(script.into())
+        }
+    }
+
+    impl Read for ScriptedReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.pop_front() {
+                Some(Ok(data)) => {
+                    buf[..data.len()].copy_from_slice(&data);
+                    Ok(data.len())
+                }
+                Some(Err(e)) => Err(e),
+                None => Ok(0),
+            }
+        }
+    }
+
+    /// A reader that sleeps before each read of the wrapped reader
+    struct DelayedReader<R> {
+        inner: R,
+        delay: Duration,
+    }
+
+    impl<R: Read> Read for DelayedReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            thread::sleep(self.delay);
+            self.inner.read(buf)
+        }
+    }
+
+    #[test]
+    fn read_timed_includes_blocking() {
+        let reader = DelayedReader {
+            inner: ScriptedReader::new(vec![Ok(vec![2, 4]), Ok(vec![210])]),
+            delay: Duration::from_millis(50),
+        };
+        let mut framed = FramedRead::new(reader, TestCodec);
+        let (msg, elapsed) = framed.framed_read_timed().unwrap();
+        assert_eq!(msg, TestMsg::U16(1234));
+        assert!(elapsed >= Duration::from_millis(100));
+        assert!(elapsed < Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn retry_empty_reads() {
+        let script = vec![Ok(vec![]), Ok(vec![]), Ok(vec![]), Ok(vec![1, 7])];
+        let mut framed = FramedRead::new(ScriptedReader::new(script), TestCodec);
+        framed.set_max_empty_reads(3);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(7));
+
+        // Giving up once the limit is reached
+        let script = vec![Ok(vec![]), Ok(vec![]), Ok(vec![]), Ok(vec![1, 7])];
+        let mut framed = FramedRead::new(ScriptedReader::new(script), TestCodec);
+        framed.set_max_empty_reads(2);
+        let err = framed.framed_read().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(err.to_string(), "too many empty reads");
+        assert_eq!(framed.get_ref().0.len(), 1);
+    }
+
+    #[test]
+    fn empty_read_is_eof_by_default() {
+        let script = vec![Ok(vec![]), Ok(vec![1, 7])];
+        let mut framed = FramedRead::new(ScriptedReader::new(script), TestCodec);
+        let err = framed.framed_read().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ConnectionReset);
+        assert_eq!(framed.get_ref().0.len(), 1);
+    }
+
+    fn would_block() -> io::Result<Vec<u8>> {
+        Err(ErrorKind::WouldBlock.into())
+    }
+
+    #[test]
+    fn retry_interrupted_read() {
+        let reader = ScriptedReader::new(vec![
+            Ok(vec![2]),
+            Err(ErrorKind::Interrupted.into()),
+            Err(ErrorKind::Interrupted.into()),
+            Ok(vec![4, 210]),
+            Err(ErrorKind::ConnectionAborted.into()),
+        ]);
+        let mut framed = FramedRead::new(reader, TestCodec);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U16(1234));
+        let err = framed.framed_read().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ConnectionAborted);
+    }
+
+    #[test]
+    fn try_read_would_block() {
+        let reader = ScriptedReader::new(vec![
+            Ok(vec![2, 4]),
+            would_block(),
+            Ok(vec![210, 1, 5]),
+            would_block(),
+            would_block(),
+            Err(ErrorKind::BrokenPipe.into()),
+        ]);
+        let mut framed = FramedRead::new(reader, TestCodec);
+        assert!(framed.try_framed_read().unwrap().is_none());
+        assert_eq!(&framed.read_buffer()[..], &[2, 4]);
+        assert_eq!(framed.try_framed_read().unwrap(), Some(TestMsg::U16(1234)));
+        assert_eq!(framed.try_framed_read().unwrap(), Some(TestMsg::U8(5)));
+        assert!(framed.try_framed_read().unwrap().is_none());
+        let err = framed.try_framed_read().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn step_until_closed() {
+        let reader = ScriptedReader::new(vec![
+            Ok(vec![2, 4]),
+            would_block(),
+            Ok(vec![210, 1, 5, 1]),
+            would_block(),
+            Ok(vec![6]),
+        ]);
+        let writer = TrickleWriter {
+            data: vec![],
+            chunk: 1,
+            ready: true,
+        };
+        let mut framed = Framed::new(reader, writer, TestCodec, TestCodec);
+        framed.reader.set_check_progress(true);
+        assert!(!framed.try_framed_write(TestMsg::U8(9)).unwrap());
+
+        let mut events = vec![];
+        loop {
+            match framed.step().unwrap() {
+                FramedEvent::Closed => break,
+                event => events.push(event),
+            }
+        }
+        assert_eq!(
+            events,
+            [
+                FramedEvent::WouldBlock,
+                FramedEvent::WouldBlock,
+                FramedEvent::ReadReady(TestMsg::U16(1234)),
+                FramedEvent::ReadReady(TestMsg::U8(5)),
+                FramedEvent::WouldBlock,
+                FramedEvent::ReadReady(TestMsg::U8(6)),
+            ]
+        );
+        assert_eq!(framed.get_ref().1.data, [1, 9]);
+        assert_eq!(framed.step().unwrap(), FramedEvent::Closed);
+    }
+
+    #[test]
+    fn buffered_and_pending_byte_counts() {
+        let reader = ScriptedReader::new(vec![Ok(vec![1, 7, 2]), Ok(vec![4]), Ok(vec![210])]);
+        let mut framed = FramedRead::new(reader, TestCodec);
+        assert_eq!(framed.bytes_buffered(), 0);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U8(7));
+        assert_eq!(framed.bytes_buffered(), 1);
+        assert!(framed.framed_read_once().unwrap().is_none());
+        assert_eq!(framed.bytes_buffered(), 2);
+        assert_eq!(framed.framed_read().unwrap(), TestMsg::U16(1234));
+        assert_eq!(framed.bytes_buffered(), 0);
+
+        let mut framed = FramedWrite::new(vec![], TestCodec);
+        framed.framed_write_buffered(TestMsg::U8(1)).unwrap();
+        framed.framed_write_buffered(TestMsg::U16(2)).unwrap();
+        assert_eq!(framed.bytes_pending(), 5);
+        framed.flush().unwrap();
+        assert_eq!(framed.bytes_pending(), 0);
+    }
+
+    /// A decoder that never consumes anything
+    struct StuckCodec {
+        frames: bool,
+    }
+
+    impl Decoder for StuckCodec {
+        type Item = ();
+        type Error = io::Error;
+
+        fn decode(&mut self, _src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+            Ok(self.frames.then_some(()))
+        }
+    }
+
+    #[test]
+    fn detect_decoder_without_progress() {
+        let r = [1u8, 2, 3];
+        let mut framed = FramedRead::new(&r[..], StuckCodec { frames: true });
+        framed.set_check_progress(true);
+        let err = framed.framed_read().unwrap_err();
+        assert_eq!(err.to_string(), "decoder made no progress");
+        assert!(framed.is_poisoned());
+
+        let mut framed = FramedRead::new(&r[..], StuckCodec { frames: false });
+        framed.set_check_progress(true);
+        assert!(framed.framed_read_once().unwrap().is_none());
+        assert!(framed.peek_frame().is_err());
+
+        // Without the check the same decoder just waits for more bytes
+        let mut framed = FramedRead::new(&r[..], StuckCodec { frames: false });
+        assert!(framed.framed_read_once().unwrap().is_none());
+        assert!(framed.peek_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn check_progress_allows_correct_decoders() {
+        let reader = ScriptedReader::new(vec![
+            Ok(vec![2]),
+            would_block(),
+            Ok(vec![4]),
+            Ok(vec![210, 1, 9]),
+        ]);
+        let mut framed = FramedRead::new(reader, TestCodec);
+        framed.set_check_progress(true);
+        assert!(framed.try_framed_read().unwrap().is_none());
+        assert_eq!(framed.framed_read_batch(None).unwrap(), vec![]);
+        assert_eq!(
+            framed.framed_read_batch(None).unwrap(),
+            vec![TestMsg::U16(1234), TestMsg::U8(9)]
+        );
+        assert!(framed.framed_read().is_err());
+        assert!(!framed.is_poisoned());
+    }
+
+    #[test]
+    fn frame_progress_mid_frame() {
+        use crate::codec::LengthDelimitedCodec;
+
+        let mut wire = vec![0, 0, 0, 10];
+        wire.extend_from_slice(&[7; 10]);
+        let reader = ScriptedReader::new(vec![
+            Ok(wire[..2].to_vec()),
+            Ok(wire[2..8].to_vec()),
+            Ok(wire[8..].to_vec()),
+        ]);
+        let mut framed = FramedRead::new(reader, LengthDelimitedCodec::new());
+        assert_eq!(framed.frame_progress(), None);
+        assert!(framed.framed_read_once().unwrap().is_none());
+        // The header is not complete yet
+        assert_eq!(framed.frame_progress(), None);
+        assert!(framed.framed_read_once().unwrap().is_none());
+        assert_eq!(framed.frame_progress(), Some((8, 14)));
+        assert_eq!(&framed.framed_read().unwrap()[..], &[7; 10]);
+        assert_eq!(framed.frame_progress(), None);
+
+        let framed = FramedRead::new(&[2u8, 4][..], TestCodec);
+        assert_eq!(framed.frame_progress(), None);
+    }
+
+    /// A message that is too big to clone
+    struct Snapshot(Vec<u8>);
+
+    impl Clone for Snapshot {
+        fn clone(&self) -> Self {
+            panic!("snapshot cloned");
+        }
+    }
+
+    struct SnapshotCodec;
+
+    impl Encoder<Snapshot> for SnapshotCodec {
+        type Error = io::Error;
+
+        fn encode(&mut self, item: Snapshot, dst: &mut BytesMut) -> Result<(), Self::Error> {
+            self.encode_ref(&item, dst)
+        }
+
+        fn encode_ref(&mut self, item: &Snapshot, dst: &mut BytesMut) -> Result<(), Self::Error> {
+            dst.put_u8(item.0.len() as u8);
+            dst.extend_from_slice(&item.0);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_borrowed_item() {
+        let snapshot = Snapshot(vec![1, 2, 3]);
+        let mut framed = FramedWrite::new(vec![], SnapshotCodec);
+        framed.framed_write_ref(&snapshot).unwrap();
+        framed.framed_write_ref(&snapshot).unwrap();
+        assert_eq!(framed.get_ref(), &[3, 1, 2, 3, 3, 1, 2, 3]);
+
+        // Codecs that do not override `encode_ref` clone the item
+        let mut framed = FramedWrite::new(vec![], crate::codec::LengthDelimitedCodec::new());
+        framed
+            .framed_write_ref(&BytesMut::from(&b"ab"[..]))
+            .unwrap();
+        assert_eq!(framed.get_ref(), &[0, 0, 0, 2, b'a', b'b']);
     }
 }
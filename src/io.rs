@@ -0,0 +1,144 @@
+//! The I/O types used by the codecs and by [`crate::driver`].
+//!
+//! With the `std` feature, the default, these are simply the ones from
+//! `std::io`. Without it this module provides a minimal stand-in: an
+//! [`Error`] that carries an [`ErrorKind`] and a static message, and
+//! [`Read`] and [`Write`] traits small enough to implement over a serial
+//! port or any other byte interface on an embedded target.
+
+#[cfg(feature = "std")]
+pub use std::io::{Error, ErrorKind, Read, Result, Write};
+
+#[cfg(not(feature = "std"))]
+pub use self::bare::{Error, ErrorKind, Read, Result, Write};
+
+#[cfg(not(feature = "std"))]
+mod bare {
+    use alloc::vec::Vec;
+    use core::fmt;
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// The kinds of error raised by the codecs and the framing driver,
+    /// named after their `std::io::ErrorKind` counterparts.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        InvalidData,
+        InvalidInput,
+        UnexpectedEof,
+        WouldBlock,
+        Interrupted,
+        WriteZero,
+        Other,
+    }
+
+    /// An I/O error: an [`ErrorKind`] and a description of what went wrong.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Error {
+        kind: ErrorKind,
+        message: &'static str,
+    }
+
+    impl Error {
+        pub fn new(kind: ErrorKind, message: &'static str) -> Self {
+            Self { kind, message }
+        }
+
+        pub fn other(message: &'static str) -> Self {
+            Self::new(ErrorKind::Other, message)
+        }
+
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    impl From<ErrorKind> for Error {
+        fn from(kind: ErrorKind) -> Self {
+            Self::new(kind, "")
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            if self.message.is_empty() {
+                write!(f, "{:?}", self.kind)
+            } else {
+                f.write_str(self.message)
+            }
+        }
+    }
+
+    impl core::error::Error for Error {}
+
+    /// A source of bytes.
+    pub trait Read {
+        /// Read some bytes into `buf`, returning how many were read. 0 means
+        /// the end of the stream has been reached.
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+    }
+
+    /// A sink for bytes.
+    pub trait Write {
+        /// Write some of `buf`, returning how many bytes were written.
+        fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+        fn flush(&mut self) -> Result<()>;
+
+        /// Write the whole of `buf`, retrying on `ErrorKind::Interrupted`.
+        fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.write(buf) {
+                    Ok(0) => {
+                        return Err(Error::new(
+                            ErrorKind::WriteZero,
+                            "failed to write whole buffer",
+                        ))
+                    }
+                    Ok(n) => buf = &buf[n..],
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            (**self).write(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            (**self).flush()
+        }
+    }
+
+    impl Read for &[u8] {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let n = buf.len().min(self.len());
+            let (head, tail) = self.split_at(n);
+            buf[..n].copy_from_slice(head);
+            *self = tail;
+            Ok(n)
+        }
+    }
+
+    impl Write for Vec<u8> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -6,10 +6,13 @@
 //! data via some add-hoc byte-orientated protocol, usually but not necessarily, over TCP.
 //! 
 //! - It supports the sending of user-defined message structures by encoding them to a
-//! byte-orientated frame through a user-defined `Encoder`.
+//!   byte-orientated frame through a user-defined `Encoder`.
 //! 
 //! - Conversely, it supports the receiving of a byte-oriented frames and decoding then through
-//! a user-defined `Decoder` into messages as understood by the application.
+//!   a user-defined `Decoder` into messages as understood by the application.
+//! 
+//! - Without the default `std` feature the crate is `no_std`, needing only `alloc`. The
+//!   codecs are still available and [`driver`] frames them over a minimal byte interface.
 //! 
 //! [`tokio::util`]: https://docs.rs/tokio-util/latest/tokio_util/
 //! 
@@ -21,6 +24,7 @@
 //!     msg2,
 //! }
 //! 
+//! #[derive(Clone)]
 //! struct MyCodec;
 //! 
 //! impl Decoder for MyCodec {
@@ -42,18 +46,39 @@
 //!     }
 //! }
 //! 
-//! let cx = TcpStream::connect("127.0.0.1:35642").unwrap();
-//! let mut rx = FramedRead(cx.try_clone()?, MyCodec);
-//! let mut tx = FramedWrite(cx, MyCodec);
+//! let mut framed = Framed::connect("127.0.0.1:35642", MyCodec).unwrap();
 //! 
 //! // Send a message
-//! tx.framed_write(MyMessage::msg1).unwrap();
+//! framed.framed_write(MyMessage::msg1).unwrap();
 //! 
 //! // Block on waiting for a message
-//! let msg = rx.framed_read().unwrap();
+//! let msg = framed.framed_read().unwrap();
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
 pub mod codec;
+#[cfg(feature = "std")]
+pub mod datagram;
+pub mod driver;
+#[cfg(feature = "std")]
 pub mod framed;
+pub mod io;
+#[cfg(feature = "std")]
+pub mod shared;
+#[cfg(feature = "std")]
+pub mod tee;
 
 pub use codec::{Decoder, Encoder};
-pub use framed::{Framed, FramedRead, FramedReader, FramedWrite, FramedWriter};
+#[cfg(feature = "std")]
+pub use datagram::{DatagramSocket, FramedDatagram};
+#[cfg(feature = "std")]
+pub use framed::{
+    Clock, Framed, FramedEvent, FramedIter, FramedListener, FramedRead, FramedReader, FramedWrite,
+    FramedWriter, ReadTimeout, ShutdownWrite, SystemClock, TcpOptions, TryClone,
+};
+#[cfg(feature = "std")]
+pub use shared::Shared;
+#[cfg(feature = "std")]
+pub use tee::Tee;
@@ -0,0 +1,129 @@
+//! A handle for using one object as both halves of a [`crate::Framed`].
+//!
+//! `Framed` keeps its reader and writer, and its decoder and encoder,
+//! separately. When a single object plays both roles, such as a stream that
+//! is both `Read` and `Write` or a codec that is both `Decoder` and
+//! `Encoder`, wrap it in a `Shared` and give a clone to each side.
+//!
+//! `Shared` is single-threaded. For a `TcpStream` used from two threads use
+//! `try_clone` as described in [`crate::framed`].
+
+use std::cell::{Ref, RefCell, RefMut};
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{Decoder, Encoder};
+
+/// A reference counted handle to a value used by both sides of a `Framed`.
+#[derive(Debug, Default)]
+pub struct Shared<T>(Rc<RefCell<T>>);
+
+impl<T> Shared<T> {
+    pub fn new(value: T) -> Self {
+        Self(Rc::new(RefCell::new(value)))
+    }
+
+    /// Immutably borrows the shared value.
+    ///
+    /// # Panics
+    /// If the value is currently mutably borrowed.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.0.borrow()
+    }
+
+    /// Mutably borrows the shared value.
+    ///
+    /// # Panics
+    /// If the value is currently borrowed.
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.0.borrow_mut()
+    }
+
+    /// Returns the shared value if this is the only handle to it.
+    /// Otherwise the handle is returned unchanged.
+    pub fn try_unwrap(self) -> Result<T, Self> {
+        Rc::try_unwrap(self.0)
+            .map(RefCell::into_inner)
+            .map_err(Self)
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self(Rc::clone(&self.0))
+    }
+}
+
+impl<T: Read> Read for Shared<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+impl<T: Write> Write for Shared<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
+
+impl<T: Decoder> Decoder for Shared<T> {
+    type Item = T::Item;
+    type Error = T::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.0.borrow_mut().decode(src)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.0.borrow_mut().decode_eof(src)
+    }
+
+    fn decode_into(
+        &mut self,
+        src: &mut BytesMut,
+        dst: &mut Self::Item,
+    ) -> Result<bool, Self::Error> {
+        self.0.borrow_mut().decode_into(src, dst)
+    }
+
+    fn decode_ready(&mut self, src: &mut BytesMut) -> Result<Vec<Self::Item>, Self::Error> {
+        self.0.borrow_mut().decode_ready(src)
+    }
+
+    fn expected_len(&self, src: &BytesMut) -> Option<usize> {
+        self.0.borrow().expected_len(src)
+    }
+
+    fn capacity_hint(&self) -> Option<usize> {
+        self.0.borrow().capacity_hint()
+    }
+}
+
+impl<I, T: Encoder<I>> Encoder<I> for Shared<T> {
+    type Error = T::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.0.borrow_mut().encode(item, dst)
+    }
+
+    fn encode_ref(&mut self, item: &I, dst: &mut BytesMut) -> Result<(), Self::Error>
+    where
+        I: Clone,
+    {
+        self.0.borrow_mut().encode_ref(item, dst)
+    }
+
+    fn encode_vectored(&mut self, item: I, bufs: &mut Vec<Bytes>) -> Result<(), Self::Error> {
+        self.0.borrow_mut().encode_vectored(item, bufs)
+    }
+
+    fn size_hint(&self, item: &I) -> Option<usize> {
+        self.0.borrow().size_hint(item)
+    }
+}
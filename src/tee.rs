@@ -0,0 +1,70 @@
+//! A reader that copies every byte it reads to a second writer.
+//!
+//! Wrapping the stream given to a [`crate::FramedRead`] in a `Tee` captures
+//! the raw bytes on the wire, before any decoding, which helps when
+//! debugging a protocol. Use [`crate::FramedRead::tee`] to add one to an
+//! existing `FramedRead`.
+
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+use crate::ReadTimeout;
+
+/// Reads from `inner`, writing a copy of everything read to `sink`.
+///
+/// An error writing to the sink is returned from `read`, and the bytes of
+/// that read are lost to the reader as well.
+#[derive(Debug)]
+pub struct Tee<R, W> {
+    inner: R,
+    sink: W,
+}
+
+impl<R, W> Tee<R, W> {
+    pub fn new(inner: R, sink: W) -> Self {
+        Self { inner, sink }
+    }
+
+    /// Returns a reference to the underlying `Read` object.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying `Read` object.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns a reference to the sink.
+    pub fn sink(&self) -> &W {
+        &self.sink
+    }
+
+    /// Returns a mutable reference to the sink.
+    pub fn sink_mut(&mut self) -> &mut W {
+        &mut self.sink
+    }
+
+    /// Consumes the `Tee`, returning the underlying `Read` object and the sink.
+    pub fn into_inner(self) -> (R, W) {
+        (self.inner, self.sink)
+    }
+}
+
+impl<R: Read, W: Write> Read for Tee<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.sink.write_all(&buf[..n])?;
+        Ok(n)
+    }
+}
+
+impl<R: ReadTimeout, W> ReadTimeout for Tee<R, W> {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.inner.read_timeout()
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+}
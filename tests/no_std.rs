@@ -0,0 +1,46 @@
+//! Uses the codecs and the framing driver from a `#![no_std]` crate, to
+//! check that they need nothing beyond `core` and `alloc`.
+//!
+//! Run with `cargo test --no-default-features --test no_std` to also build
+//! the library itself without `std`.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use bytes::BytesMut;
+use framous::codec::{CobsCodec, LengthDelimitedCodec, LinesCodec};
+use framous::driver::{FrameReader, FrameWriter};
+use framous::io::ErrorKind;
+use framous::{Decoder, Encoder};
+
+#[test]
+fn codecs_without_std() {
+    let mut codec = LinesCodec::new();
+    let mut buf = BytesMut::new();
+    codec.encode("first", &mut buf).unwrap();
+    codec.encode("second", &mut buf).unwrap();
+    assert_eq!(codec.decode(&mut buf).unwrap().unwrap(), "first");
+    assert_eq!(codec.decode(&mut buf).unwrap().unwrap(), "second");
+    assert!(codec.decode(&mut buf).unwrap().is_none());
+
+    let mut buf = BytesMut::from(&[0, 0, 0, 9][..]);
+    let err = LengthDelimitedCodec::builder()
+        .max_frame_length(4)
+        .new_codec()
+        .decode(&mut buf)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn driver_without_std() {
+    let mut writer = FrameWriter::new(Vec::new(), CobsCodec::new());
+    writer.write_frame(BytesMut::from(&[1, 0, 2][..])).unwrap();
+    let wire = writer.into_inner();
+
+    let mut reader = FrameReader::new(&wire[..], CobsCodec::new());
+    assert_eq!(&reader.read_frame().unwrap().unwrap()[..], &[1, 0, 2]);
+    assert!(reader.read_frame().unwrap().is_none());
+}